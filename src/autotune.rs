@@ -0,0 +1,180 @@
+use crate::pid::{PidGains, OUTPUT_MAX};
+use crate::temperature::Celsius;
+use crate::TICK_HZ;
+use core::f32::consts::PI;
+use serde::{Deserialize, Serialize};
+
+// relay switches this far either side of the setpoint so noise can't chatter it
+const HYSTERESIS: Celsius = Celsius(100);
+// give up if the oven overshoots the setpoint by this much
+pub const ABORT_MARGIN: Celsius = Celsius::from_degrees(25);
+// seconds, a cold oven that can't reach the setpoint in time never will
+const MAX_DURATION: u64 = 45 * 60;
+// the first cycle starts from ambient so it is thrown away
+const IGNORED_CYCLES: u8 = 1;
+const MEASURED_CYCLES: u8 = 3;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TuningRule {
+    #[default]
+    ZieglerNichols,
+    TyreusLuyben,
+    SomeOvershoot,
+    NoOvershoot,
+}
+
+impl TuningRule {
+    pub fn from_index(idx: u8) -> Option<TuningRule> {
+        match idx {
+            0 => Some(TuningRule::ZieglerNichols),
+            1 => Some(TuningRule::TyreusLuyben),
+            2 => Some(TuningRule::SomeOvershoot),
+            3 => Some(TuningRule::NoOvershoot),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TuningRule::ZieglerNichols => "ZIEGLER-NICHOLS",
+            TuningRule::TyreusLuyben => "TYREUS-LUYBEN",
+            TuningRule::SomeOvershoot => "SOME OVERSHOOT",
+            TuningRule::NoOvershoot => "NO OVERSHOOT",
+        }
+    }
+
+    // (kp / ku, ti / pu, td / pu)
+    fn factors(&self) -> (f32, f32, f32) {
+        match self {
+            TuningRule::ZieglerNichols => (0.6, 0.5, 0.125),
+            TuningRule::TyreusLuyben => (1.0 / 2.2, 2.2, 1.0 / 6.3),
+            TuningRule::SomeOvershoot => (0.33, 0.5, 0.33),
+            TuningRule::NoOvershoot => (0.2, 0.5, 0.33),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AutotuneError {
+    OverTemp,
+    Timeout,
+}
+
+impl AutotuneError {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            AutotuneError::OverTemp => "TOO HOT",
+            AutotuneError::Timeout => "TIMED OUT",
+        }
+    }
+}
+
+// ultimate gain in %/C and period in seconds
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ultimate {
+    pub gain: f32,
+    pub period: f32,
+}
+
+impl Ultimate {
+    pub fn gains(&self, rule: TuningRule) -> PidGains {
+        let (kp, ti, td) = rule.factors();
+        let kp = kp * self.gain;
+        PidGains {
+            kp,
+            ki: kp / (ti * self.period),
+            kd: kp * td * self.period,
+        }
+    }
+}
+
+// bang-bang around the setpoint and watch how the oven swings, no hardware in here
+pub struct Autotune {
+    setpoint: Celsius,
+    started_at: u64,
+    heating: bool,
+    // extremes of the swing in progress, low while heating and high while cooling
+    low: Celsius,
+    high: Celsius,
+    // tick the heater last came on
+    last_on: u64,
+    cycles: u8,
+    amplitude_sum: Celsius,
+    period_sum: u64,
+}
+
+impl Autotune {
+    pub fn new(setpoint: Celsius, now: u64) -> Autotune {
+        Autotune {
+            setpoint,
+            started_at: now,
+            heating: true,
+            low: setpoint,
+            high: setpoint,
+            last_on: now,
+            cycles: 0,
+            amplitude_sum: Celsius::default(),
+            period_sum: 0,
+        }
+    }
+
+    pub fn setpoint(&self) -> Celsius {
+        self.setpoint
+    }
+
+    // heater on or off, the relay is driven fully either way
+    pub fn heating(&self) -> bool {
+        self.heating
+    }
+
+    pub fn cycles(&self) -> u8 {
+        self.cycles
+    }
+
+    pub fn total_cycles(&self) -> u8 {
+        IGNORED_CYCLES + MEASURED_CYCLES
+    }
+
+    // Some once enough cycles have been measured
+    pub fn update(&mut self, temp: Celsius, now: u64) -> Result<Option<Ultimate>, AutotuneError> {
+        if temp > self.setpoint + ABORT_MARGIN {
+            return Err(AutotuneError::OverTemp);
+        }
+        if now - self.started_at > MAX_DURATION * TICK_HZ {
+            return Err(AutotuneError::Timeout);
+        }
+
+        if self.heating {
+            self.low = self.low.min(temp);
+            if temp > self.setpoint + HYSTERESIS {
+                self.heating = false;
+                self.high = temp;
+            }
+            return Ok(None);
+        }
+
+        self.high = self.high.max(temp);
+        if temp >= self.setpoint - HYSTERESIS {
+            return Ok(None);
+        }
+
+        // a full swing is done once the heater comes back on
+        self.cycles += 1;
+        if self.cycles > IGNORED_CYCLES {
+            self.amplitude_sum = self.amplitude_sum + (self.high - self.low) / 2;
+            self.period_sum += now - self.last_on;
+        }
+        self.heating = true;
+        self.low = temp;
+        self.last_on = now;
+
+        if self.cycles < IGNORED_CYCLES + MEASURED_CYCLES {
+            return Ok(None);
+        }
+        let amplitude = self.amplitude_sum.0 as f32 / 100.0 / MEASURED_CYCLES as f32;
+        let period = self.period_sum as f32 / TICK_HZ as f32 / MEASURED_CYCLES as f32;
+        // describing function of a relay swinging the output by +-OUTPUT_MAX/2
+        let gain = 4.0 * (OUTPUT_MAX / 2.0) / (PI * amplitude.max(0.01));
+        Ok(Some(Ultimate { gain, period }))
+    }
+}
@@ -0,0 +1,53 @@
+use crate::temperature::Celsius;
+use serde::{Deserialize, Serialize};
+
+// gain is stored in parts per GAIN_ONE
+pub const GAIN_ONE: i32 = 10000;
+// references closer together than this can't give a meaningful gain
+const MIN_SPAN: Celsius = Celsius::from_degrees(20);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Calibration {
+    pub offset: Celsius,
+    pub gain: i32,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Calibration {
+            offset: Celsius(0),
+            gain: GAIN_ONE,
+        }
+    }
+}
+
+impl Calibration {
+    fn scale(&self, raw: Celsius) -> Celsius {
+        Celsius((raw.0 as i64 * self.gain as i64 / GAIN_ONE as i64) as i32)
+    }
+
+    pub fn apply(&self, raw: Celsius) -> Celsius {
+        self.scale(raw) + self.offset
+    }
+
+    // each point is (what the sensor read, what the reference says)
+    pub fn from_two_points(
+        low: (Celsius, Celsius),
+        high: (Celsius, Celsius),
+    ) -> Option<Calibration> {
+        let (low, high) = if low.0 <= high.0 { (low, high) } else { (high, low) };
+        let measured_span = high.0 - low.0;
+        let reference_span = high.1 - low.1;
+        if measured_span < MIN_SPAN || reference_span < MIN_SPAN {
+            return None;
+        }
+
+        let gain = (reference_span.0 as i64 * GAIN_ONE as i64 / measured_span.0 as i64) as i32;
+        let mut calibration = Calibration {
+            offset: Celsius(0),
+            gain,
+        };
+        calibration.offset = low.1 - calibration.scale(low.0);
+        Some(calibration)
+    }
+}
@@ -0,0 +1,64 @@
+use core::fmt::{Display, Formatter};
+use core::ops::{Add, Div, Mul, Sub};
+use serde::{Deserialize, Serialize};
+
+// hundredths of a degree, fine enough for every chip we support
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Celsius(pub i32);
+
+impl Celsius {
+    pub const fn from_degrees(degrees: i32) -> Celsius {
+        Celsius(degrees * 100)
+    }
+
+    // raw reading with `bits` fractional bits, e.g. 2 for quarter degrees
+    pub const fn from_fixed(raw: i32, bits: u32) -> Celsius {
+        Celsius((raw * 100) >> bits)
+    }
+
+    pub const fn degrees(&self) -> i32 {
+        self.0 / 100
+    }
+}
+
+impl Add for Celsius {
+    type Output = Celsius;
+
+    fn add(self, rhs: Celsius) -> Celsius {
+        Celsius(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Celsius {
+    type Output = Celsius;
+
+    fn sub(self, rhs: Celsius) -> Celsius {
+        Celsius(self.0 - rhs.0)
+    }
+}
+
+impl Mul<i32> for Celsius {
+    type Output = Celsius;
+
+    fn mul(self, rhs: i32) -> Celsius {
+        Celsius(self.0 * rhs)
+    }
+}
+
+impl Div<i32> for Celsius {
+    type Output = Celsius;
+
+    fn div(self, rhs: i32) -> Celsius {
+        Celsius(self.0 / rhs)
+    }
+}
+
+impl Display for Celsius {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        if self.0 < 0 {
+            write!(f, "-")?;
+        }
+        let abs = self.0.unsigned_abs();
+        write!(f, "{}.{:02}", abs / 100, abs % 100)
+    }
+}
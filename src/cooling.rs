@@ -0,0 +1,99 @@
+use crate::pid::OUTPUT_MAX;
+use crate::temperature::Celsius;
+use crate::TICK_HZ;
+use serde::{Deserialize, Serialize};
+
+// cooling rate is measured over this many ticks, any shorter and it's mostly noise
+const RATE_TICKS: u64 = TICK_HZ * 2;
+// percent the brake duty moves by every rate measurement
+const BRAKE_STEP: f32 = 10.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoolingSettings {
+    // fan stays on until the oven is below this
+    pub safe_temp: Celsius,
+    pub open_door: Celsius,
+    // tenths of a degree a second, big BGAs crack if they cool faster
+    pub max_rate: u8,
+}
+
+impl Default for CoolingSettings {
+    fn default() -> Self {
+        CoolingSettings {
+            safe_temp: Celsius::from_degrees(50),
+            open_door: Celsius::from_degrees(180),
+            max_rate: 30,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CoolingStage {
+    Cooling,
+    OpenDoor,
+    SafeToHandle,
+}
+
+impl CoolingStage {
+    pub fn from_temp(settings: &CoolingSettings, temp: Celsius) -> CoolingStage {
+        if temp < settings.safe_temp {
+            CoolingStage::SafeToHandle
+        } else if temp < settings.open_door {
+            CoolingStage::OpenDoor
+        } else {
+            CoolingStage::Cooling
+        }
+    }
+
+    pub fn prompt(&self) -> &'static str {
+        match self {
+            CoolingStage::Cooling => "FAN ON",
+            CoolingStage::OpenDoor => "OPEN DOOR",
+            CoolingStage::SafeToHandle => "SAFE TO HANDLE",
+        }
+    }
+}
+
+// runs the fan after a profile and nudges the heater if the oven drops too fast
+#[derive(Default)]
+pub struct Cooling {
+    last: Option<(Celsius, u64)>,
+    // percent
+    brake: f32,
+}
+
+impl Cooling {
+    pub fn new() -> Cooling {
+        Cooling::default()
+    }
+
+    // heater duty to hold the cooling rate down, in percent
+    pub fn brake(&self) -> f32 {
+        self.brake
+    }
+
+    pub fn update(&mut self, settings: &CoolingSettings, temp: Celsius, now: u64) -> CoolingStage {
+        let stage = CoolingStage::from_temp(settings, temp);
+        if stage == CoolingStage::SafeToHandle {
+            self.brake = 0.0;
+            return stage;
+        }
+
+        match self.last {
+            Some((last, at)) if now - at >= RATE_TICKS => {
+                // hundredths of a degree a second, positive while cooling
+                let rate = (last - temp).0 as i64 * TICK_HZ as i64 / (now - at) as i64;
+                if rate > settings.max_rate as i64 * 10 {
+                    self.brake += BRAKE_STEP;
+                } else {
+                    self.brake -= BRAKE_STEP;
+                }
+                self.brake = self.brake.clamp(0.0, OUTPUT_MAX);
+                self.last = Some((temp, now));
+            }
+            Some(_) => {}
+            None => self.last = Some((temp, now)),
+        }
+        stage
+    }
+}
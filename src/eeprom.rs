@@ -0,0 +1,55 @@
+use avrd::current::{EEARH, EEARL};
+use ruduino::cores::current::{EECR, EEDR};
+use ruduino::interrupt::without_interrupts;
+use ruduino::Register;
+
+// atmega328 has 1KiB of EEPROM
+pub const EEPROM_SIZE: u16 = 1024;
+
+fn set_address(addr: u16) {
+    let [high, low] = addr.to_be_bytes();
+    unsafe {
+        *EEARH = high;
+    }
+    unsafe {
+        *EEARL = low;
+    }
+}
+
+fn wait_ready() {
+    while EECR::is_set(EECR::EEPE) {}
+}
+
+pub fn read_byte(addr: u16) -> u8 {
+    wait_ready();
+    set_address(addr);
+    unsafe { EECR::set(EECR::EERE) }
+    EEDR::read()
+}
+
+pub fn write_byte(addr: u16, data: u8) {
+    // every write wears the cell, skip the ones that wouldn't change anything
+    if read_byte(addr) == data {
+        return;
+    }
+    wait_ready();
+    set_address(addr);
+    unsafe { EEDR::write(data) }
+    // EEPE has to follow EEMPE within 4 cycles
+    without_interrupts(|| unsafe {
+        EECR::set(EECR::EEMPE);
+        EECR::set(EECR::EEPE);
+    });
+}
+
+pub fn read_bytes(addr: u16, data: &mut [u8]) {
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = read_byte(addr + i as u16);
+    }
+}
+
+pub fn write_bytes(addr: u16, data: &[u8]) {
+    for (i, byte) in data.iter().enumerate() {
+        write_byte(addr + i as u16, *byte);
+    }
+}
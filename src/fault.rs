@@ -0,0 +1,84 @@
+use crate::runaway::Runaway;
+use crate::temperature::TemperatureError;
+use crate::tracking::Deviation;
+
+// anything that forces the heater off until the operator acknowledges it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    Sensor(TemperatureError),
+    NtcSensor(TemperatureError),
+    NtcDisagree,
+    NtcOverTemp,
+    // index of the point that was never reached
+    PointTimeout(u8),
+    Tracking(Deviation),
+    Runaway(Runaway),
+    OverTemp,
+    // refused to start, a point is above the max temperature
+    ProfileOverTemp,
+    // the main loop hung and the watchdog reset us
+    Watchdog,
+    // same, but because of a panic, the location is kept in EEPROM
+    Panic,
+}
+
+impl Fault {
+    // top line of the fault screen, the reason goes under it
+    pub fn title(&self) -> &'static str {
+        match self {
+            Fault::Watchdog | Fault::Panic => "RECOVERED FROM",
+            _ => "FAULT! HEAT OFF",
+        }
+    }
+
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Fault::Sensor(why) => why.reason(),
+            Fault::NtcSensor(_) => "NTC BROKEN",
+            Fault::NtcDisagree => "NTC DISAGREES",
+            Fault::NtcOverTemp => "NTC OVER TEMP",
+            Fault::PointTimeout(_) => "POINT TIMED OUT",
+            Fault::Tracking(why) => why.reason(),
+            Fault::Runaway(why) => why.reason(),
+            Fault::OverTemp => "OVER MAX TEMP",
+            Fault::ProfileOverTemp => "PROFILE TOO HOT",
+            Fault::Watchdog => "FAULT",
+            Fault::Panic => "PANIC",
+        }
+    }
+
+    // what goes in the fault log
+    pub fn code(&self) -> u8 {
+        match self {
+            Fault::Sensor(_) => 1,
+            Fault::NtcSensor(_) => 2,
+            Fault::NtcDisagree => 3,
+            Fault::NtcOverTemp => 4,
+            Fault::PointTimeout(_) => 5,
+            Fault::Tracking(_) => 6,
+            Fault::Runaway(_) => 7,
+            Fault::OverTemp => 8,
+            Fault::ProfileOverTemp => 9,
+            Fault::Watchdog => 10,
+            Fault::Panic => 11,
+        }
+    }
+
+    // for reading the log back, the details aren't in it
+    pub fn describe(code: u8) -> &'static str {
+        match code {
+            1 => "SENSOR",
+            2 => "NTC BROKEN",
+            3 => "NTC DISAGREES",
+            4 => "NTC OVER TEMP",
+            5 => "POINT TIMEOUT",
+            6 => "OFF PROFILE",
+            7 => "RUNAWAY",
+            8 => "OVER MAX TEMP",
+            9 => "PROFILE TOO HOT",
+            10 => "WATCHDOG RESET",
+            11 => "PANIC",
+            _ => "UNKNOWN",
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use crate::eeprom;
+
+// right after the settings, up to where the panic record starts
+const LOG_ADDR: u16 = 0x3C0;
+const LOG_LEN: u16 = 0x30;
+// first byte is where the next code goes, the rest wraps around
+const LOG_SLOTS: u16 = LOG_LEN - 1;
+
+pub fn record(code: u8) {
+    let mut next = eeprom::read_byte(LOG_ADDR) as u16;
+    // blank EEPROM reads back 0xFF
+    if next >= LOG_SLOTS {
+        next = 0;
+    }
+    eeprom::write_byte(LOG_ADDR + 1 + next, code);
+    eeprom::write_byte(LOG_ADDR, ((next + 1) % LOG_SLOTS) as u8);
+}
+
+// `back` entries before the newest, None past the oldest
+pub fn recent(back: u16) -> Option<u8> {
+    let next = eeprom::read_byte(LOG_ADDR) as u16;
+    if next >= LOG_SLOTS || back >= LOG_SLOTS {
+        return None;
+    }
+    let slot = (next + LOG_SLOTS - 1 - back) % LOG_SLOTS;
+    // slots that were never written are still blank
+    match eeprom::read_byte(LOG_ADDR + 1 + slot) {
+        0xFF => None,
+        code => Some(code),
+    }
+}
@@ -0,0 +1,123 @@
+use crate::temperature::Celsius;
+use crate::TICK_HZ;
+use serde::{Deserialize, Serialize};
+
+pub const MAX_MEDIAN_WINDOW: usize = 7;
+// after this many rejected samples in a row the jump is real, take it
+const MAX_REJECTED: u8 = 3;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterKind {
+    #[default]
+    None,
+    Median,
+    Ema,
+}
+
+impl FilterKind {
+    pub fn from_index(idx: u8) -> Option<FilterKind> {
+        match idx {
+            0 => Some(FilterKind::None),
+            1 => Some(FilterKind::Median),
+            2 => Some(FilterKind::Ema),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FilterKind::None => "NONE",
+            FilterKind::Median => "MEDIAN",
+            FilterKind::Ema => "EMA",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilterSettings {
+    pub kind: FilterKind,
+    // samples, 1 to MAX_MEDIAN_WINDOW
+    pub median_window: u8,
+    // out of 256, higher follows the input faster
+    pub ema_alpha: u8,
+    // degrees per second, 0 turns spike rejection off
+    pub max_rate: u8,
+}
+
+impl Default for FilterSettings {
+    fn default() -> Self {
+        FilterSettings {
+            kind: FilterKind::None,
+            median_window: 5,
+            ema_alpha: 64,
+            max_rate: 20,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Filter {
+    window: [Celsius; MAX_MEDIAN_WINDOW],
+    window_len: usize,
+    window_next: usize,
+    ema: Option<Celsius>,
+    last_accepted: Option<(Celsius, u64)>,
+    rejected: u8,
+    output: Option<Celsius>,
+}
+
+impl Filter {
+    pub fn new() -> Filter {
+        Filter::default()
+    }
+
+    pub fn reset(&mut self) {
+        *self = Filter::default();
+    }
+
+    pub fn value(&self) -> Option<Celsius> {
+        self.output
+    }
+
+    // `now` is in timer ticks
+    pub fn update(&mut self, settings: &FilterSettings, raw: Celsius, now: u64) -> Celsius {
+        if let Some((last, at)) = self.last_accepted {
+            let change = (raw - last).0.unsigned_abs() as u64;
+            // both sides in hundredths of a degree times ticks
+            let allowed = settings.max_rate as u64 * 100 * (now - at).max(1);
+            if settings.max_rate != 0
+                && change * TICK_HZ > allowed
+                && self.rejected < MAX_REJECTED
+            {
+                self.rejected += 1;
+                return self.output.unwrap_or(last);
+            }
+        }
+        self.rejected = 0;
+        self.last_accepted = Some((raw, now));
+
+        let window = (settings.median_window as usize).clamp(1, MAX_MEDIAN_WINDOW);
+        self.window[self.window_next % window] = raw;
+        self.window_next = (self.window_next + 1) % window;
+        self.window_len = (self.window_len + 1).min(window);
+
+        let ema = match self.ema {
+            Some(ema) => ema + (raw - ema) * settings.ema_alpha as i32 / 256,
+            None => raw,
+        };
+        self.ema = Some(ema);
+
+        let filtered = match settings.kind {
+            FilterKind::None => raw,
+            FilterKind::Median => {
+                let mut sorted = self.window;
+                let sorted = &mut sorted[..self.window_len];
+                sorted.sort_unstable();
+                sorted[sorted.len() / 2]
+            }
+            FilterKind::Ema => ema,
+        };
+        self.output = Some(filtered);
+        filtered
+    }
+}
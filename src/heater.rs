@@ -0,0 +1,41 @@
+use crate::pid::OUTPUT_MAX;
+use crate::setpoint::{Phase, NUM_PHASES};
+use serde::{Deserialize, Serialize};
+
+pub const NUM_HEATERS: usize = 2;
+pub const HEATER_NAMES: [&str; NUM_HEATERS] = ["BOTTOM", "TOP"];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeaterSettings {
+    // percent of the controller output each element gets, by phase
+    pub balance: [[u8; NUM_HEATERS]; NUM_PHASES],
+    // percent, added after the balance
+    pub bias: [i8; NUM_HEATERS],
+}
+
+impl Default for HeaterSettings {
+    fn default() -> Self {
+        HeaterSettings {
+            // bottom heavy until reflow, top off so the board cools from above
+            balance: [[100, 60], [100, 80], [100, 100], [100, 0]],
+            bias: [0; NUM_HEATERS],
+        }
+    }
+}
+
+impl HeaterSettings {
+    // split one controller output into a duty for every element
+    pub fn duties(&self, phase: Phase, output: f32) -> [u8; NUM_HEATERS] {
+        let balance = self.balance[phase.index()];
+        let mut duties = [0; NUM_HEATERS];
+        for heater in 0..NUM_HEATERS {
+            // bias never turns an element on by itself once the controller wants off
+            if output <= 0.0 {
+                continue;
+            }
+            let duty = output * balance[heater] as f32 / 100.0 + self.bias[heater] as f32;
+            duties[heater] = duty.clamp(0.0, OUTPUT_MAX) as u8;
+        }
+        duties
+    }
+}
@@ -1,28 +1,62 @@
 #![feature(abi_avr_interrupt)]
+#![feature(asm_experimental_arch)]
 #![no_std]
 
 extern crate alloc;
 
 use crate::lcd::LCDHardware;
-use crate::temperature::Temperature;
+use crate::settings::Settings;
+use crate::temperature::{
+    setup_sensors, Averaging, Celsius, ControlSource, MainsFilter, Ntc, ThermocoupleType,
+    TemperatureError, Units, NUM_SENSORS, SENSORS, SENSOR_NAMES,
+};
 use ::lcd::Display;
 use avr_delay::delay_ms;
 use avrd::atmega328::PORTB;
-use avrd::current::{EEARH, EEARL};
-use core::cmp::Ordering;
 use core::ptr::{read_volatile, write_volatile};
 use core::sync::atomic::{AtomicU64, Ordering as MemOrdering};
-use ruduino::cores::current::{EEAR, EECR, EEDR};
 
-use crate::profile::{CurvePoint, Profile, Profiles};
-use ruduino::cores::current::port::{C0, C1, C2, C3, C4, C6, D2, D3};
-use ruduino::{Pin, Register};
+use crate::autotune::{Autotune, AutotuneError, TuningRule};
+use crate::calibration::Calibration;
+use crate::cooling::{Cooling, CoolingStage};
+use crate::fault::Fault;
+use crate::filter::{Filter, FilterKind, MAX_MEDIAN_WINDOW};
+use crate::heater::{HEATER_NAMES, NUM_HEATERS};
+use crate::output::{HeaterOutput, Output, OutputMode, OutputSettings, TimeProportional};
+use crate::phaseangle::Channel;
+use crate::pid::{Pid, KD_STEP, KI_STEP, KP_STEP};
+use crate::profile::{
+    Advance, CurvePoint, Profile, Profiles, PROFILES_HEADER_LEN, PROFILES_MAX_LEN, TIMEOUT_STEP,
+};
+use crate::runaway::RunawayMonitor;
+use crate::sampler::{Sampler, CONVERSION_TICKS};
+use crate::setpoint::{Clock, Phase};
+use crate::tracking::{Monitor, Tracking};
+use ruduino::cores::current::port::{C0, C1, C2, C3, C4, C5, C6, D1, D2, D3};
+use ruduino::Pin;
 use sb_rotary_encoder::{Direction, RotaryEncoder};
+use smdtoaster::{celsius, pid, profile, pwm, setpoint, thermocouple};
 
+mod autotune;
+mod calibration;
+mod cooling;
+mod eeprom;
+mod fault;
+mod faultlog;
+mod filter;
+mod heater;
 mod lcd;
-mod profile;
+mod output;
+mod panic;
+mod phaseangle;
+mod runaway;
+mod sampler;
+mod settings;
 mod temperature;
+mod tracking;
 mod ui;
+mod watchdog;
+mod zerocross;
 
 #[derive(Default)]
 enum UiState {
@@ -30,6 +64,8 @@ enum UiState {
     MainMenu,
     ProfileEdit,
     OvenRun,
+    Config,
+    Fault,
 }
 
 #[derive(Default)]
@@ -43,6 +79,8 @@ enum ProfileEditSubMenus {
     ProfilePointTempEdit,
     ProfilePointTimeEdit,
     ProfilePointDisabledEdit,
+    ProfilePointAdvanceEdit,
+    ProfilePointTimeoutEdit,
     ProfileExitConfirmMenu,
     ProfileWriteConfirmMenu,
 }
@@ -54,84 +92,194 @@ enum OvenRunSubMenus {
     OvenProfileRunConfirm,
     OvenProfileRunningMenu,
     OvenProfileCancelRunningMenu,
+    OvenCoolingMenu,
+}
+
+#[derive(Default)]
+enum ConfigSubMenus {
+    #[default]
+    ConfigSelect,
+    ThermocoupleTypeEdit,
+    AveragingEdit,
+    MainsFilterEdit,
+    FilterKindEdit,
+    MedianWindowEdit,
+    EmaAlphaEdit,
+    MaxRateEdit,
+    ControlSourceEdit,
+    NtcMarginEdit,
+    NtcLimitEdit,
+    MaxTempEdit,
+    UnitsEdit,
+    KpEdit,
+    KiEdit,
+    KdEdit,
+    LookaheadEdit,
+    FeedForwardEdit,
+    HeatPeriodEdit,
+    HeatMinOnEdit,
+    HeatMinOffEdit,
+    HeatModeEdit,
+    BalancePhaseSelect,
+    BalanceEdit,
+    BiasEdit,
+    SafeTempEdit,
+    OpenDoorEdit,
+    MaxCoolRateEdit,
+    TrackPhaseSelect,
+    TrackAboveEdit,
+    TrackBelowEdit,
+    TrackWarnEdit,
+    TrackAbortEdit,
+    BuzzerEdit,
+    RunawayRiseEdit,
+    RunawayPeriodEdit,
+    RunawayMarginEdit,
+    TuningRuleEdit,
+    AutotuneSetpointEdit,
+    AutotuneRunning,
+    AutotuneDone,
+    Diagnostics,
+    FaultLog,
+    CalibrateSensorSelect,
+    CalibrateLow,
+    CalibrateHigh,
+    CalibrateConfirm,
 }
 
 const PULSE_DIVIDER: i32 = 4;
 
+pub const TICK_HZ: u64 = 100;
+
+// timer1 ticks, see TICK_HZ
+static TICKS: AtomicU64 = AtomicU64::new(0);
+// seconds
 static OYASUMI_TIME: AtomicU64 = AtomicU64::new(0);
 
+type FanRelay = C3;
+// bottom element
+type HeaterRelay = C4;
+type TopHeaterRelay = C5;
+// optional, active buzzer. every other pin is taken so it shares TX with the serial port,
+// it's only driven when the settings say one is fitted
+type BuzzerPin = D1;
+
+static HEATER_OUTPUT: HeaterOutput<HeaterRelay> = HeaterOutput::new(Channel::A);
+static TOP_HEATER_OUTPUT: HeaterOutput<TopHeaterRelay> = HeaterOutput::new(Channel::B);
+static FAN_OUTPUT: TimeProportional<FanRelay> = TimeProportional::new();
+
+// same order as HEATER_NAMES
+static HEATER_OUTPUTS: [&(dyn Output + Sync); NUM_HEATERS] = [&HEATER_OUTPUT, &TOP_HEATER_OUTPUT];
+
+fn heaters_off() {
+    HEATER_OUTPUTS.iter().for_each(|heater| heater.off());
+}
+
+fn configure_heaters(settings: &OutputSettings) {
+    HEATER_OUTPUTS
+        .iter()
+        .for_each(|heater| heater.configure(settings));
+}
+
 #[no_mangle]
 pub unsafe extern "avr-interrupt" fn _ivr_timer1_compare_a() {
-    OYASUMI_TIME.fetch_add(1, MemOrdering::SeqCst);
+    let ticks = TICKS.fetch_add(1, MemOrdering::SeqCst) + 1;
+    if ticks % TICK_HZ == 0 {
+        OYASUMI_TIME.fetch_add(1, MemOrdering::SeqCst);
+        zerocross::second();
+    }
+    HEATER_OUTPUT.tick();
+    TOP_HEATER_OUTPUT.tick();
+    FAN_OUTPUT.tick();
+}
+
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn _ivr_timer2_compare_a() {
+    HEATER_OUTPUT.fire();
+}
+
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn _ivr_timer2_compare_b() {
+    TOP_HEATER_OUTPUT.fire();
+}
+
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn _ivr_pin_change_2() {
+    if zerocross::crossed() {
+        phaseangle::start_half_cycle();
+        HEATER_OUTPUT.cross();
+        TOP_HEATER_OUTPUT.cross();
+    }
 }
 
 fn main() {
+    // whatever state a reset left them in, nothing is on until the loop says so
+    HeaterRelay::set_low();
+    TopHeaterRelay::set_low();
+    FanRelay::set_low();
+    HeaterRelay::set_output();
+    TopHeaterRelay::set_output();
+    FanRelay::set_output();
+    let watchdog_reset = watchdog::take_reset();
+
     // 1602 LCD
     let hw = LCDHardware {};
     let mut display = Display::new(hw);
 
-    // K type
-    Temperature::setup();
+    // K type, MAX6675 or MAX31855 depending on build
+    setup_sensors();
+    // backup thermistor
+    Ntc::setup();
+    // optional, only used for burst fire and phase angle
+    zerocross::setup();
+    phaseangle::setup();
+
+    let mut settings = Settings::load();
+    configure_heaters(&settings.heater_output);
+    FAN_OUTPUT.configure(&OutputSettings::default());
+    #[cfg(feature = "max31856")]
+    temperature::configure_sensors(
+        settings.thermocouple_type,
+        settings.averaging,
+        settings.mains_filter,
+    );
 
-    type FanRelay = C3;
-    type HeaterRelay = C4;
     type ButtonPin = C2;
     type SWPin = C1;
     type APin = D3;
     type BPin = D2;
 
-    FanRelay::set_output();
-    HeaterRelay::set_output();
     ButtonPin::set_input();
     SWPin::set_input();
     APin::set_input();
     BPin::set_input();
 
-    const DESIRED_HZ_TIM1: f64 = 2.0;
-    const TIM1_PRESCALER: u64 = 1024;
-    const INTERRUPT_EVERY_1_HZ_1024_PRESCALER: u16 = ((ruduino::config::CPU_FREQUENCY_HZ as f64
+    const DESIRED_HZ_TIM1: f64 = TICK_HZ as f64;
+    const TIM1_PRESCALER: u64 = 256;
+    const INTERRUPT_EVERY_TICK_256_PRESCALER: u16 = ((ruduino::config::CPU_FREQUENCY_HZ as f64
         / (DESIRED_HZ_TIM1 * TIM1_PRESCALER as f64))
         as u64
         - 1) as u16;
 
     timer1::Timer::new()
         .waveform_generation_mode(timer1::WaveformGenerationMode::ClearOnTimerMatchOutputCompare)
-        .clock_source(timer1::ClockSource::Prescale1024)
-        .output_compare_1(Some(INTERRUPT_EVERY_1_HZ_1024_PRESCALER))
+        .clock_source(timer1::ClockSource::Prescale256)
+        .output_compare_1(Some(INTERRUPT_EVERY_TICK_256_PRESCALER))
         .configure();
 
-    // read first 2 bytes of EEPROM
-    unsafe {
-        *EEARH = 0b0000_0000_u8;
-    }
-    unsafe {
-        *EEARL = 0b0000_0000_u8;
-    }
-    unsafe { EECR::set(EECR::EERE) }
-    let low_byte = EEDR::read();
-    unsafe {
-        *EEARL = 0b0000_0001_u8;
-    }
-    unsafe { EECR::set(EECR::EERE) }
-    let high_byte = EEDR::read();
-    let num_bytes = u16::from_le_bytes([high_byte, low_byte]) + 3;
-
+    // length, format, blob
     let mut profiles = {
-        let mut data = [0_u8; 1000];
-
-        for addr in 3..num_bytes {
-            let split: [u8; 2] = addr.to_le_bytes();
-            unsafe {
-                *EEARH = split[0];
-            }
-            unsafe {
-                *EEARL = split[1];
-            }
-            unsafe { EECR::set(EECR::EERE) }
-            data[addr] = EEDR::read();
+        let mut header = [0_u8; PROFILES_HEADER_LEN as usize];
+        eeprom::read_bytes(0, &mut header);
+        let len = u16::from_be_bytes([header[0], header[1]]) as usize;
+        let mut data = [0_u8; PROFILES_MAX_LEN];
+        // blank EEPROM reads back 0xFF
+        if len <= PROFILES_MAX_LEN {
+            eeprom::read_bytes(PROFILES_HEADER_LEN, &mut data[..len]);
+            Profiles::decode(header[2], &data[..len])
+        } else {
+            Profiles::default()
         }
-
-        postcard::from_bytes::<Profiles>(&data[0..num_bytes - 3]).unwrap()
     };
 
     let mut rotary = RotaryEncoder::new();
@@ -148,45 +296,83 @@ fn main() {
         name: [b' ', b' ', b' ', b' ', b' ', b' '],
         points: [
             CurvePoint {
-                temp: 0,
+                temp: Celsius::default(),
                 time_seconds: 0,
                 disabled: true,
+                advance: Advance::Time,
+                timeout: 0,
             },
             CurvePoint {
-                temp: 0,
+                temp: Celsius::default(),
                 time_seconds: 0,
                 disabled: true,
+                advance: Advance::Time,
+                timeout: 0,
             },
             CurvePoint {
-                temp: 0,
+                temp: Celsius::default(),
                 time_seconds: 0,
                 disabled: true,
+                advance: Advance::Time,
+                timeout: 0,
             },
             CurvePoint {
-                temp: 0,
+                temp: Celsius::default(),
                 time_seconds: 0,
                 disabled: true,
+                advance: Advance::Time,
+                timeout: 0,
             },
             CurvePoint {
-                temp: 0,
+                temp: Celsius::default(),
                 time_seconds: 0,
                 disabled: true,
+                advance: Advance::Time,
+                timeout: 0,
             },
             CurvePoint {
-                temp: 0,
+                temp: Celsius::default(),
                 time_seconds: 0,
                 disabled: true,
+                advance: Advance::Time,
+                timeout: 0,
             },
         ],
     };
     let mut idx = 0;
     let mut idx1 = 0;
     let mut oven_run_state = OvenRunSubMenus::default();
+    let mut config_state = ConfigSubMenus::default();
     let mut changed = false;
     let mut running_oven = false;
     let mut time_left = 0;
     let mut current_start_time = 0;
-    let mut current_pt = 0;
+    let mut run_start_temp = Celsius::default();
+    let mut run_clock = Clock::new();
+    let mut monitor = Monitor::new();
+    let mut runaway = RunawayMonitor::new();
+    let mut latched_fault: Option<Fault> = None;
+    // raised from a menu, latched with the rest at the top of the next loop
+    let mut menu_fault: Option<Fault> = None;
+    // stays until the fault screen is acknowledged, even across power cycles
+    let mut panic_record = panic::last();
+    let recovered = match panic_record {
+        Some(_) => Some(Fault::Panic),
+        None if watchdog_reset => Some(Fault::Watchdog),
+        None => None,
+    };
+    if let Some(fault) = recovered {
+        // only logged the first time round
+        if watchdog_reset {
+            faultlog::record(fault.code());
+        }
+        latched_fault = Some(fault);
+        ui_state = UiState::Fault;
+        changed = true;
+    }
+    let mut pid = Pid::new();
+    let mut heater_duty = 0.0;
+    let mut last_control = 0;
 
     write!(display, "BOOTING...").unwrap();
     delay_ms(2000);
@@ -195,12 +381,43 @@ fn main() {
     let mut input_b = BPin::is_high();
     let mut button = ButtonPin::is_high();
     let mut alt_button = SWPin::is_high();
-    let mut temp = Temperature::read_temperature();
+    let mut samplers = SENSORS.map(Sampler::new);
+    let mut filters: [Filter; NUM_SENSORS] = Default::default();
+    let mut raw_temps = [Err(TemperatureError::Stale); NUM_SENSORS];
+    let mut temps = raw_temps;
+    let mut temp = Err(TemperatureError::Stale);
+    let mut calibration_sensor = 0;
+    // (measured, reference) for the first calibration point
+    let mut calibration_low = (Celsius::default(), Celsius::default());
+    let mut calibration_result = None;
+    let mut autotune: Option<Autotune> = None;
+    let mut cooling: Option<Cooling> = None;
+    let mut balance_phase = Phase::Preheat;
+    let mut track_phase = Phase::Preheat;
+    let mut heater_idx = 0;
+    // counter the gain editor opened on, left alone the gain is kept exactly as tuned
+    let mut gain_entry = 0_u8;
+    let mut autotune_result: Result<(), AutotuneError> = Ok(());
     let mut direction = Direction::Clockwise;
+    let mut last_kick = 0;
 
+    watchdog::enable();
     loop {
-        // read inputs
-        temp = Temperature::read_temperature();
+        // read inputs, the thermocouples only when a conversion is done
+        let now = TICKS.load(MemOrdering::SeqCst);
+        for sensor in 0..NUM_SENSORS {
+            let calibration = settings.calibration[sensor];
+            let fresh = samplers[sensor].poll(now);
+            raw_temps[sensor] = samplers[sensor].temperature(now);
+            if let (true, Ok(raw)) = (fresh, raw_temps[sensor]) {
+                filters[sensor].update(&settings.filter, calibration.apply(raw), now);
+            }
+            // faults always come straight through, only good readings get smoothed
+            temps[sensor] = raw_temps[sensor]
+                .map(|raw| filters[sensor].value().unwrap_or_else(|| calibration.apply(raw)));
+        }
+        temp = settings.control_source.select(&temps);
+        let ntc_temp = Ntc::read_temperature();
         input_a = APin::is_high();
         input_b = BPin::is_high();
         button = ButtonPin::is_high();
@@ -222,48 +439,188 @@ fn main() {
             }
         }
 
-        if running_oven {
-            if !FanRelay::is_high() {
-                FanRelay::set_high();
+        let mut new_fault = Ntc::check(ntc_temp, temp, &settings.ntc)
+            .err()
+            .or(menu_fault.take());
+        // hard limit on every probe, whatever the profile or the controller think
+        if temps
+            .iter()
+            .flatten()
+            .any(|probe| *probe > settings.max_temp)
+        {
+            new_fault = Some(Fault::OverTemp);
+        }
+        if running_oven || autotune.is_some() || cooling.is_some() {
+            if let Err(why) = temp {
+                // never keep heating on a reading we can't trust
+                new_fault = Some(Fault::Sensor(why));
             }
-            // Temperature, decide if our current point
-            if let Some(profile) = &profiles.profiles[run_profile_idx as usize] {
-                let next_point = profile.points[current_pt + 1];
-                let this_point = profile.points[current_pt];
-                let time = OYASUMI_TIME.load(MemOrdering::SeqCst);
-                time_left = time - current_start_time;
-
-                let target = (next_point.temp - this_point.temp)
-                    / (next_point.time_seconds - this_point.time_seconds);
-                match target.cmp(&temp) {
-                    Ordering::Less => {
-                        HeaterRelay::set_low();
-                    }
-                    Ordering::Equal => {
-                        HeaterRelay::set_high();
+        }
+        let mut tracking = Tracking::Following;
+        if running_oven {
+            if let (Some(profile), Ok(temp)) = (&profiles.profiles[run_profile_idx as usize], temp)
+            {
+                let elapsed = OYASUMI_TIME.load(MemOrdering::SeqCst) - current_start_time;
+                if let Err(point) = run_clock.update(profile, run_start_temp, elapsed, temp) {
+                    // the oven can't get there, don't carry on into the next step cold
+                    new_fault = Some(Fault::PointTimeout(point));
+                }
+                let curve_time = run_clock.curve_time(elapsed);
+                if let Some(setpoint) =
+                    setpoint::setpoint(profile, run_start_temp, curve_time, &settings.lookahead)
+                {
+                    // waiting at a point is off the curve on purpose, its timeout covers that
+                    if run_clock.waiting() {
+                        monitor.reset();
+                    } else {
+                        tracking = monitor.update(
+                            &settings.tracking,
+                            setpoint.phase,
+                            setpoint.current,
+                            temp,
+                            now,
+                        );
                     }
-                    Ordering::Greater => {
-                        HeaterRelay::set_high();
+                    if let Tracking::Abort(why) = tracking {
+                        new_fault = Some(Fault::Tracking(why));
                     }
                 }
+            }
+        }
+        if let Ok(temp) = temp {
+            let duty = HEATER_OUTPUTS
+                .iter()
+                .map(|heater| heater.duty())
+                .max()
+                .unwrap_or(0);
+            if let Err(why) = runaway.update(&settings.runaway, duty, temp, now) {
+                new_fault = Some(Fault::Runaway(why));
+            }
+        }
+        // beep on and off while warning, hands TX back as soon as the buzzer is turned off
+        if !settings.tracking.buzzer {
+            BuzzerPin::set_input();
+        } else if matches!(tracking, Tracking::Warn(_)) && (now / (TICK_HZ / 2)) % 2 == 0 {
+            BuzzerPin::set_output();
+            BuzzerPin::set_high();
+        } else {
+            BuzzerPin::set_output();
+            BuzzerPin::set_low();
+        }
+        if let (None, Some(fault)) = (latched_fault, new_fault) {
+            faultlog::record(fault.code());
+            latched_fault = Some(fault);
+            oven_run_state = OvenRunSubMenus::OvenProfileSelect;
+            ui_state = UiState::Fault;
+            running_oven = false;
+            time_left = 0;
+            current_start_time = 0;
+            autotune = None;
+            cooling = None;
+            config_state = ConfigSubMenus::ConfigSelect;
+            ui_counter = 0;
+            changed = true;
+        }
+        if latched_fault.is_some() {
+            // heater off and air moving until someone acknowledges it
+            heaters_off();
+            FAN_OUTPUT.set_duty(100);
+        } else if running_oven {
+            FAN_OUTPUT.set_duty(100);
+            // Temperature, decide if our current point
+            if let (Some(profile), Ok(temp)) = (&profiles.profiles[run_profile_idx as usize], temp) {
+                let elapsed = OYASUMI_TIME.load(MemOrdering::SeqCst) - current_start_time;
+                let curve_time = run_clock.curve_time(elapsed);
+                time_left = setpoint::duration(profile).saturating_sub(curve_time as u16) as u64;
 
-                if next_point.time_seconds <= time as u16 {
-                    current_pt += 1;
-                    if current_pt >= 5 {
-                        oven_run_state = OvenRunSubMenus::OvenProfileSelect;
-                        ui_state = UiState::MainMenu;
+                match setpoint::setpoint(profile, run_start_temp, curve_time, &settings.lookahead) {
+                    Some(setpoint) => {
+                        // no point running faster than new readings come in
+                        if now - last_control >= CONVERSION_TICKS {
+                            let dt = (now - last_control) as f32 / TICK_HZ as f32;
+                            let target = setpoint.target.min(settings.max_temp);
+                            let feed_forward = setpoint.feed_forward(&settings.lookahead);
+                            heater_duty = pid.update(&settings.pid, target, temp, feed_forward, dt);
+                            last_control = now;
+                        }
+                        let duties = settings.heaters.duties(setpoint.phase, heater_duty);
+                        for (heater, duty) in HEATER_OUTPUTS.iter().zip(duties) {
+                            heater.set_duty(duty);
+                        }
+                    }
+                    None => {
+                        oven_run_state = OvenRunSubMenus::OvenCoolingMenu;
                         running_oven = false;
+                        cooling = Some(Cooling::new());
                         time_left = 0;
                         current_start_time = 0;
+                        changed = true;
+                    }
+                }
+            }
+        } else if let (Some(tune), Ok(temp)) = (&mut autotune, temp) {
+            // same airflow as a real run or the numbers are for a different oven
+            FAN_OUTPUT.set_duty(100);
+            let result = tune.update(temp, now);
+            if tune.heating() {
+                HEATER_OUTPUTS
+                    .iter()
+                    .for_each(|heater| heater.set_duty(100));
+            } else {
+                heaters_off();
+            }
+            match result {
+                Ok(None) => {}
+                Ok(Some(ultimate)) => {
+                    settings.pid = ultimate.gains(settings.tuning_rule);
+                    // only the gains, anything else changed in the menu still needs a SAVE
+                    let mut stored = Settings::load();
+                    stored.pid = settings.pid;
+                    stored.save();
+                    autotune_result = Ok(());
+                    autotune = None;
+                    config_state = ConfigSubMenus::AutotuneDone;
+                    changed = true;
+                }
+                Err(why) => {
+                    heaters_off();
+                    autotune_result = Err(why);
+                    autotune = None;
+                    config_state = ConfigSubMenus::AutotuneDone;
+                    changed = true;
+                }
+            }
+        } else if let (Some(cool), Ok(temp)) = (&mut cooling, temp) {
+            match cool.update(&settings.cooling, temp, now) {
+                CoolingStage::SafeToHandle => {
+                    heaters_off();
+                    FAN_OUTPUT.set_duty(0);
+                    cooling = None;
+                }
+                _ => {
+                    FAN_OUTPUT.set_duty(100);
+                    let duties = settings.heaters.duties(Phase::Cooling, cool.brake());
+                    for (heater, duty) in HEATER_OUTPUTS.iter().zip(duties) {
+                        heater.set_duty(duty);
                     }
                 }
             }
+        } else {
+            heaters_off();
+            FAN_OUTPUT.set_duty(0);
         }
 
         if clocks % display_update == 0 {
             match ui_state {
                 UiState::MainMenu => {
-                    ui::main_menu(&mut display, temp, ui_counter, changed);
+                    ui::main_menu(
+                        &mut display,
+                        temp,
+                        samplers[0].cold_junction(),
+                        ui_counter,
+                        settings.units,
+                        changed,
+                    );
                     if changed {
                         changed = false;
                     }
@@ -277,6 +634,10 @@ fn main() {
                                 ui_state = UiState::ProfileEdit;
                                 changed = true
                             }
+                            2 => {
+                                ui_state = UiState::Config;
+                                changed = true
+                            }
                             _ => {
                                 ui_counter = 0;
                             }
@@ -387,6 +748,7 @@ fn main() {
                             &mut display,
                             ui_counter,
                             &profile_editing_temp_profile.points,
+                            settings.units,
                             changed,
                         );
                         if changed {
@@ -420,6 +782,7 @@ fn main() {
                             ui_counter,
                             &profile_editing_temp_profile.points[idx1 as usize],
                             idx1,
+                            settings.units,
                             changed,
                         );
                         if changed {
@@ -444,6 +807,18 @@ fn main() {
                                         ProfileEditSubMenus::ProfilePointDisabledEdit;
                                 }
                                 3 => {
+                                    changed = true;
+                                    ui_counter = 0;
+                                    profile_edit_state =
+                                        ProfileEditSubMenus::ProfilePointAdvanceEdit;
+                                }
+                                4 => {
+                                    changed = true;
+                                    ui_counter = 0;
+                                    profile_edit_state =
+                                        ProfileEditSubMenus::ProfilePointTimeoutEdit;
+                                }
+                                5 => {
                                     changed = true;
                                     ui_counter = 0;
                                     profile_edit_state = ProfileEditSubMenus::ProfilePointSelect;
@@ -459,6 +834,8 @@ fn main() {
                             ui_counter,
                             &profile_editing_temp_profile.points[idx1 as usize],
                             idx1,
+                            settings.max_temp,
+                            settings.units,
                             changed,
                         );
                         if rst {
@@ -467,9 +844,12 @@ fn main() {
                         if changed {
                             changed = false;
                         }
-                        if button {
-                            profile_editing_temp_profile.points[idx as usize].temp =
-                                ui_counter as u16;
+                        let entered = settings
+                            .units
+                            .from_counter(ui_counter, settings.units.entry_step());
+                        // over the max isn't taken, the screen says why
+                        if button && entered <= settings.max_temp {
+                            profile_editing_temp_profile.points[idx as usize].temp = entered;
                             ui_counter = 0;
                             changed = true;
                             profile_edit_state = ProfileEditSubMenus::ProfilePointSelectElementEdit;
@@ -520,6 +900,50 @@ fn main() {
                             profile_edit_state = ProfileEditSubMenus::ProfilePointSelectElementEdit;
                         }
                     }
+                    ProfileEditSubMenus::ProfilePointAdvanceEdit => {
+                        let rst = ui::edit_profile_point_edit_advance_menu(
+                            &mut display,
+                            ui_counter,
+                            &profile_editing_temp_profile.points[idx1 as usize],
+                            idx1,
+                            changed,
+                        );
+                        if !rst {
+                            ui_counter = 0;
+                        }
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            profile_editing_temp_profile.points[idx1 as usize].advance =
+                                Advance::from_index(ui_counter).unwrap_or_default();
+                            ui_counter = 0;
+                            changed = true;
+                            profile_edit_state = ProfileEditSubMenus::ProfilePointSelectElementEdit;
+                        }
+                    }
+                    ProfileEditSubMenus::ProfilePointTimeoutEdit => {
+                        let rst = ui::edit_profile_point_edit_timeout_menu(
+                            &mut display,
+                            ui_counter,
+                            &profile_editing_temp_profile.points[idx1 as usize],
+                            idx1,
+                            changed,
+                        );
+                        if !rst {
+                            ui_counter = 0;
+                        }
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            profile_editing_temp_profile.points[idx1 as usize].timeout =
+                                ui_counter as u16 * TIMEOUT_STEP;
+                            ui_counter = 0;
+                            changed = true;
+                            profile_edit_state = ProfileEditSubMenus::ProfilePointSelectElementEdit;
+                        }
+                    }
                     ProfileEditSubMenus::ProfileExitConfirmMenu => {
                         ui::edit_exit_menu(&mut display, ui_counter, changed);
                         if changed {
@@ -623,23 +1047,53 @@ fn main() {
                                     changed = true;
                                 }
                                 1 => {
+                                    let peak = profiles.profiles[run_profile_idx as usize]
+                                        .and_then(|profile| setpoint::peak(&profile));
+                                    if peak > Some(settings.max_temp) {
+                                        menu_fault = Some(Fault::ProfileOverTemp);
+                                        continue;
+                                    }
+                                    match temp {
+                                        Ok(temp) => run_start_temp = temp,
+                                        Err(why) => {
+                                            menu_fault = Some(Fault::Sensor(why));
+                                            continue;
+                                        }
+                                    }
                                     oven_run_state = OvenRunSubMenus::OvenProfileRunningMenu;
                                     ui_counter = 0;
                                     changed = true;
                                     running_oven = true;
+                                    cooling = None;
                                     current_start_time = OYASUMI_TIME.load(MemOrdering::SeqCst);
+                                    run_clock = Clock::new();
+                                    monitor.reset();
+                                    pid.reset();
+                                    last_control = now;
                                 }
                                 _ => ui_counter = 0,
                             }
                         }
                     }
                     OvenRunSubMenus::OvenProfileRunningMenu => {
+                        // the synced modes quietly drop back to slow PWM, say so
+                        let warning = match tracking {
+                            Tracking::Warn(why) => Some(why.reason()),
+                            _ => None,
+                        }
+                        .or((settings.heater_output.mode != OutputMode::TimeProportional
+                            && !zerocross::present())
+                        .then_some("NO ZERO CROSS"))
+                        .or(run_clock.waiting().then_some("WAITING FOR TEMP"));
                         ui::heating_menu(
                             &mut display,
                             ui_counter,
-                            temp,
+                            temp.unwrap_or_default(),
+                            &temps,
                             &current_running_profile,
                             time_left as u16,
+                            warning,
+                            settings.units,
                             changed,
                         );
                         if changed {
@@ -670,18 +1124,1172 @@ fn main() {
                                 }
                                 1 => {
                                     ui_counter = 0;
-                                    oven_run_state = OvenRunSubMenus::OvenProfileSelect;
-                                    ui_state = UiState::MainMenu;
+                                    // still hot, cool down the same as a finished run
+                                    oven_run_state = OvenRunSubMenus::OvenCoolingMenu;
                                     running_oven = false;
+                                    cooling = Some(Cooling::new());
                                     time_left = 0;
                                     current_start_time = 0;
+                                    changed = true;
                                 }
                                 _ => ui_counter = 0,
                             }
                         }
                     }
+                    OvenRunSubMenus::OvenCoolingMenu => {
+                        let temp = temp.unwrap_or_default();
+                        ui::cooling_menu(
+                            &mut display,
+                            temp,
+                            CoolingStage::from_temp(&settings.cooling, temp),
+                            settings.units,
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        // the fan carries on by itself if we leave early
+                        if button {
+                            ui_counter = 0;
+                            oven_run_state = OvenRunSubMenus::OvenProfileSelect;
+                            ui_state = UiState::MainMenu;
+                            changed = true;
+                        }
+                    }
                 },
-            }
+                UiState::Fault => {
+                    match (latched_fault, &panic_record) {
+                        (Some(Fault::Panic), Some(record)) => {
+                            ui::panic_menu(&mut display, record, changed);
+                        }
+                        (Some(fault), _) => {
+                            ui::fault_menu(&mut display, fault, changed);
+                        }
+                        _ => {}
+                    }
+                    if changed {
+                        changed = false;
+                    }
+                    if button {
+                        // acknowledged, if it's still there it latches again next time round
+                        if latched_fault == Some(Fault::Panic) {
+                            panic::clear();
+                            panic_record = None;
+                        }
+                        latched_fault = None;
+                        ui_counter = 0;
+                        changed = true;
+                        ui_state = UiState::MainMenu;
+                        // still hot, keep the fan going until it's safe to handle
+                        if let Ok(temp) = temp {
+                            if temp > settings.cooling.safe_temp {
+                                cooling = Some(Cooling::new());
+                                oven_run_state = OvenRunSubMenus::OvenCoolingMenu;
+                                ui_state = UiState::OvenRun;
+                            }
+                        }
+                    }
+                }
+                UiState::Config => match config_state {
+                    ConfigSubMenus::ConfigSelect => {
+                        let rst = ui::config_menu(&mut display, ui_counter, changed);
+                        if !rst {
+                            ui_counter = 0;
+                        }
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            let item = ui_counter;
+                            ui_counter = 0;
+                            changed = true;
+                            match item {
+                                0 => config_state = ConfigSubMenus::ThermocoupleTypeEdit,
+                                1 => config_state = ConfigSubMenus::AveragingEdit,
+                                2 => config_state = ConfigSubMenus::MainsFilterEdit,
+                                3 => config_state = ConfigSubMenus::FilterKindEdit,
+                                4 => {
+                                    ui_counter = settings.filter.median_window;
+                                    config_state = ConfigSubMenus::MedianWindowEdit;
+                                }
+                                5 => {
+                                    ui_counter = settings.filter.ema_alpha;
+                                    config_state = ConfigSubMenus::EmaAlphaEdit;
+                                }
+                                6 => {
+                                    ui_counter = settings.units.delta_to_counter(
+                                        Celsius::from_degrees(settings.filter.max_rate as i32),
+                                        100,
+                                    );
+                                    config_state = ConfigSubMenus::MaxRateEdit;
+                                }
+                                7 => config_state = ConfigSubMenus::ControlSourceEdit,
+                                8 => {
+                                    ui_counter =
+                                        settings.units.delta_to_counter(settings.ntc.margin, 100);
+                                    config_state = ConfigSubMenus::NtcMarginEdit;
+                                }
+                                9 => {
+                                    ui_counter = settings.units.to_counter(
+                                        settings.ntc.limit,
+                                        settings.units.entry_step() * 2,
+                                    );
+                                    config_state = ConfigSubMenus::NtcLimitEdit;
+                                }
+                                10 => {
+                                    ui_counter = settings.units.to_counter(
+                                        settings.max_temp,
+                                        settings.units.entry_step() * 2,
+                                    );
+                                    config_state = ConfigSubMenus::MaxTempEdit;
+                                }
+                                11 => config_state = ConfigSubMenus::UnitsEdit,
+                                12 => {
+                                    ui_counter = (settings.pid.kp / KP_STEP).round() as u8;
+                                    gain_entry = ui_counter;
+                                    config_state = ConfigSubMenus::KpEdit;
+                                }
+                                13 => {
+                                    ui_counter = (settings.pid.ki / KI_STEP).round() as u8;
+                                    gain_entry = ui_counter;
+                                    config_state = ConfigSubMenus::KiEdit;
+                                }
+                                14 => {
+                                    ui_counter = (settings.pid.kd / KD_STEP).round() as u8;
+                                    gain_entry = ui_counter;
+                                    config_state = ConfigSubMenus::KdEdit;
+                                }
+                                15 => {
+                                    ui_counter = settings.lookahead.time;
+                                    config_state = ConfigSubMenus::LookaheadEdit;
+                                }
+                                16 => {
+                                    ui_counter = settings.lookahead.feed_forward;
+                                    config_state = ConfigSubMenus::FeedForwardEdit;
+                                }
+                                17 => {
+                                    ui_counter = settings.heater_output.period;
+                                    config_state = ConfigSubMenus::HeatPeriodEdit;
+                                }
+                                18 => {
+                                    ui_counter = settings.heater_output.min_on;
+                                    config_state = ConfigSubMenus::HeatMinOnEdit;
+                                }
+                                19 => {
+                                    ui_counter = settings.heater_output.min_off;
+                                    config_state = ConfigSubMenus::HeatMinOffEdit;
+                                }
+                                20 => config_state = ConfigSubMenus::HeatModeEdit,
+                                21 => config_state = ConfigSubMenus::BalancePhaseSelect,
+                                22 => {
+                                    heater_idx = 0;
+                                    ui_counter = (settings.heaters.bias[0] as i16 + 100) as u8;
+                                    config_state = ConfigSubMenus::BiasEdit;
+                                }
+                                23 => {
+                                    ui_counter = settings.units.to_counter(
+                                        settings.cooling.safe_temp,
+                                        settings.units.entry_step(),
+                                    );
+                                    config_state = ConfigSubMenus::SafeTempEdit;
+                                }
+                                24 => {
+                                    ui_counter = settings.units.to_counter(
+                                        settings.cooling.open_door,
+                                        settings.units.entry_step(),
+                                    );
+                                    config_state = ConfigSubMenus::OpenDoorEdit;
+                                }
+                                25 => {
+                                    // stored in tenths of a degree a second
+                                    ui_counter = settings.units.delta_to_counter(
+                                        Celsius(settings.cooling.max_rate as i32 * 10),
+                                        10,
+                                    );
+                                    config_state = ConfigSubMenus::MaxCoolRateEdit;
+                                }
+                                26 => config_state = ConfigSubMenus::TrackPhaseSelect,
+                                27 => {
+                                    ui_counter = settings.tracking.warn_after;
+                                    config_state = ConfigSubMenus::TrackWarnEdit;
+                                }
+                                28 => {
+                                    ui_counter = settings.tracking.abort_after;
+                                    config_state = ConfigSubMenus::TrackAbortEdit;
+                                }
+                                29 => config_state = ConfigSubMenus::BuzzerEdit,
+                                30 => {
+                                    ui_counter =
+                                        settings.units.delta_to_counter(settings.runaway.rise, 100);
+                                    config_state = ConfigSubMenus::RunawayRiseEdit;
+                                }
+                                31 => {
+                                    ui_counter = settings.runaway.period;
+                                    config_state = ConfigSubMenus::RunawayPeriodEdit;
+                                }
+                                32 => {
+                                    ui_counter = settings
+                                        .units
+                                        .delta_to_counter(settings.runaway.margin, 100);
+                                    config_state = ConfigSubMenus::RunawayMarginEdit;
+                                }
+                                33 => config_state = ConfigSubMenus::TuningRuleEdit,
+                                34 => {
+                                    ui_counter = settings.units.to_counter(
+                                        Celsius::from_degrees(150),
+                                        settings.units.entry_step() * 2,
+                                    );
+                                    config_state = ConfigSubMenus::AutotuneSetpointEdit;
+                                }
+                                35 => config_state = ConfigSubMenus::Diagnostics,
+                                36 => config_state = ConfigSubMenus::FaultLog,
+                                37 => config_state = ConfigSubMenus::CalibrateSensorSelect,
+                                38 => {
+                                    settings.calibration = [Calibration::default(); NUM_SENSORS];
+                                    filters.iter_mut().for_each(Filter::reset);
+                                }
+                                39 => {
+                                    settings.save();
+                                    configure_heaters(&settings.heater_output);
+                                    filters.iter_mut().for_each(Filter::reset);
+                                    #[cfg(feature = "max31856")]
+                                    temperature::configure_sensors(
+                                        settings.thermocouple_type,
+                                        settings.averaging,
+                                        settings.mains_filter,
+                                    );
+                                    ui_state = UiState::MainMenu;
+                                }
+                                _ => {
+                                    // throw away anything that wasn't saved
+                                    settings = Settings::load();
+                                    ui_state = UiState::MainMenu;
+                                }
+                            }
+                        }
+                    }
+                    ConfigSubMenus::ThermocoupleTypeEdit => {
+                        if ThermocoupleType::from_index(ui_counter).is_none() {
+                            ui_counter = 0;
+                        }
+                        let choice = ThermocoupleType::from_index(ui_counter).unwrap_or_default();
+                        ui::config_value_menu(&mut display, "TC TYPE", choice.name(), changed);
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.thermocouple_type = choice;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::AveragingEdit => {
+                        if Averaging::from_index(ui_counter).is_none() {
+                            ui_counter = 0;
+                        }
+                        let choice = Averaging::from_index(ui_counter).unwrap_or_default();
+                        ui::config_value_menu(&mut display, "TC AVERAGE", choice.name(), changed);
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.averaging = choice;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::MainsFilterEdit => {
+                        if MainsFilter::from_index(ui_counter).is_none() {
+                            ui_counter = 0;
+                        }
+                        let choice = MainsFilter::from_index(ui_counter).unwrap_or_default();
+                        ui::config_value_menu(&mut display, "MAINS HZ", choice.name(), changed);
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.mains_filter = choice;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::FilterKindEdit => {
+                        if FilterKind::from_index(ui_counter).is_none() {
+                            ui_counter = 0;
+                        }
+                        let choice = FilterKind::from_index(ui_counter).unwrap_or_default();
+                        ui::config_value_menu(&mut display, "FILTER", choice.name(), changed);
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.filter.kind = choice;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::MedianWindowEdit => {
+                        ui_counter = ui_counter.clamp(1, MAX_MEDIAN_WINDOW as u8);
+                        ui::config_number_menu(
+                            &mut display,
+                            "MEDIAN WINDOW",
+                            ui_counter as i32,
+                            "SAMPLES",
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.filter.median_window = ui_counter;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::EmaAlphaEdit => {
+                        ui_counter = ui_counter.max(1);
+                        ui::config_number_menu(
+                            &mut display,
+                            "EMA ALPHA",
+                            ui_counter as i32,
+                            "/256",
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.filter.ema_alpha = ui_counter;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::MaxRateEdit => {
+                        ui::config_number_menu(
+                            &mut display,
+                            "SPIKE LIMIT",
+                            ui_counter as i32,
+                            settings.units.rate_suffix(),
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            let limit = settings.units.delta_from_counter(ui_counter, 100);
+                            settings.filter.max_rate = limit.degrees().min(u8::MAX as i32) as u8;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::ControlSourceEdit => {
+                        if ControlSource::from_index(ui_counter).is_none() {
+                            ui_counter = 0;
+                        }
+                        let choice = ControlSource::from_index(ui_counter).unwrap_or_default();
+                        ui::config_value_menu(&mut display, "CONTROL ON", choice.name(), changed);
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.control_source = choice;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::NtcMarginEdit => {
+                        ui::config_number_menu(
+                            &mut display,
+                            "NTC MARGIN",
+                            ui_counter as i32,
+                            settings.units.suffix(),
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.ntc.margin =
+                                settings.units.delta_from_counter(ui_counter, 100);
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::NtcLimitEdit => {
+                        // double steps a click to get past 255
+                        let limit = settings
+                            .units
+                            .from_counter(ui_counter, settings.units.entry_step() * 2);
+                        ui::config_number_menu(
+                            &mut display,
+                            "NTC LIMIT",
+                            settings.units.whole(limit),
+                            settings.units.suffix(),
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.ntc.limit = limit;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::MaxTempEdit => {
+                        let max_temp = settings
+                            .units
+                            .from_counter(ui_counter, settings.units.entry_step() * 2);
+                        ui::config_number_menu(
+                            &mut display,
+                            "MAX TEMP",
+                            settings.units.whole(max_temp),
+                            settings.units.suffix(),
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.max_temp = max_temp;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::UnitsEdit => {
+                        if Units::from_index(ui_counter).is_none() {
+                            ui_counter = 0;
+                        }
+                        let choice = Units::from_index(ui_counter).unwrap_or_default();
+                        ui::config_value_menu(&mut display, "UNITS", choice.name(), changed);
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.units = choice;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::KpEdit => {
+                        let kp = if ui_counter == gain_entry {
+                            settings.pid.kp
+                        } else {
+                            ui_counter as f32 * KP_STEP
+                        };
+                        ui::config_number_menu(
+                            &mut display,
+                            "PID KP",
+                            ui::Fixed((kp * 10.0) as i32, 1),
+                            "%/C",
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.pid.kp = kp;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::KiEdit => {
+                        let ki = if ui_counter == gain_entry {
+                            settings.pid.ki
+                        } else {
+                            ui_counter as f32 * KI_STEP
+                        };
+                        ui::config_number_menu(
+                            &mut display,
+                            "PID KI",
+                            ui::Fixed((ki * 1000.0) as i32, 3),
+                            "%/CS",
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.pid.ki = ki;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::KdEdit => {
+                        let kd = if ui_counter == gain_entry {
+                            settings.pid.kd
+                        } else {
+                            ui_counter as f32 * KD_STEP
+                        };
+                        ui::config_number_menu(&mut display, "PID KD", kd as i32, "%S/C", changed);
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.pid.kd = kd;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::LookaheadEdit => {
+                        ui::config_number_menu(
+                            &mut display,
+                            "LOOKAHEAD",
+                            ui_counter as i32,
+                            "S",
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.lookahead.time = ui_counter;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::FeedForwardEdit => {
+                        ui::config_number_menu(
+                            &mut display,
+                            "FEED FWD",
+                            ui_counter as i32,
+                            "%S/C",
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.lookahead.feed_forward = ui_counter;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::HeatPeriodEdit => {
+                        ui_counter = ui_counter.max(1);
+                        ui::config_number_menu(
+                            &mut display,
+                            "HEAT PERIOD",
+                            ui::Fixed(ui_counter as i32, 1),
+                            "S",
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.heater_output.period = ui_counter;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::HeatMinOnEdit => {
+                        ui::config_number_menu(
+                            &mut display,
+                            "HEAT MIN ON",
+                            ui::Fixed(ui_counter as i32, 1),
+                            "S",
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.heater_output.min_on = ui_counter;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::HeatMinOffEdit => {
+                        ui::config_number_menu(
+                            &mut display,
+                            "HEAT MIN OFF",
+                            ui::Fixed(ui_counter as i32, 1),
+                            "S",
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.heater_output.min_off = ui_counter;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::HeatModeEdit => {
+                        if OutputMode::from_index(ui_counter).is_none() {
+                            ui_counter = 0;
+                        }
+                        let choice = OutputMode::from_index(ui_counter).unwrap_or_default();
+                        ui::config_value_menu(&mut display, "HEAT MODE", choice.name(), changed);
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.heater_output.mode = choice;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::BalancePhaseSelect => {
+                        if Phase::from_index(ui_counter).is_none() {
+                            ui_counter = 0;
+                        }
+                        let choice = Phase::from_index(ui_counter).unwrap_or(Phase::Preheat);
+                        ui::config_value_menu(&mut display, "BALANCE FOR", choice.name(), changed);
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            balance_phase = choice;
+                            heater_idx = 0;
+                            ui_counter = settings.heaters.balance[choice.index()][0];
+                            changed = true;
+                            config_state = ConfigSubMenus::BalanceEdit;
+                        }
+                    }
+                    ConfigSubMenus::BalanceEdit => {
+                        ui_counter = ui_counter.min(100);
+                        ui::config_number_menu(
+                            &mut display,
+                            HEATER_NAMES[heater_idx],
+                            ui_counter as i32,
+                            "%",
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.heaters.balance[balance_phase.index()][heater_idx] =
+                                ui_counter;
+                            heater_idx += 1;
+                            changed = true;
+                            // one element after the other, then back out
+                            if heater_idx < NUM_HEATERS {
+                                ui_counter =
+                                    settings.heaters.balance[balance_phase.index()][heater_idx];
+                            } else {
+                                ui_counter = 0;
+                                config_state = ConfigSubMenus::ConfigSelect;
+                            }
+                        }
+                    }
+                    ConfigSubMenus::BiasEdit => {
+                        // counter 0 is -100%
+                        ui_counter = ui_counter.min(200);
+                        ui::config_number_menu(
+                            &mut display,
+                            HEATER_NAMES[heater_idx],
+                            ui_counter as i32 - 100,
+                            "% BIAS",
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.heaters.bias[heater_idx] = (ui_counter as i16 - 100) as i8;
+                            heater_idx += 1;
+                            changed = true;
+                            if heater_idx < NUM_HEATERS {
+                                ui_counter = (settings.heaters.bias[heater_idx] as i16 + 100) as u8;
+                            } else {
+                                ui_counter = 0;
+                                config_state = ConfigSubMenus::ConfigSelect;
+                            }
+                        }
+                    }
+                    ConfigSubMenus::SafeTempEdit => {
+                        let safe_temp = settings
+                            .units
+                            .from_counter(ui_counter, settings.units.entry_step());
+                        ui::config_number_menu(
+                            &mut display,
+                            "SAFE TEMP",
+                            settings.units.whole(safe_temp),
+                            settings.units.suffix(),
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.cooling.safe_temp = safe_temp;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::OpenDoorEdit => {
+                        let open_door = settings
+                            .units
+                            .from_counter(ui_counter, settings.units.entry_step());
+                        ui::config_number_menu(
+                            &mut display,
+                            "OPEN DOOR AT",
+                            settings.units.whole(open_door),
+                            settings.units.suffix(),
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.cooling.open_door = open_door;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::MaxCoolRateEdit => {
+                        ui::config_number_menu(
+                            &mut display,
+                            "MAX COOL RATE",
+                            ui::Fixed(ui_counter as i32, 1),
+                            settings.units.rate_suffix(),
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            let rate = settings.units.delta_from_counter(ui_counter, 10);
+                            settings.cooling.max_rate = (rate.0 / 10).min(u8::MAX as i32) as u8;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::TrackPhaseSelect => {
+                        if Phase::from_index(ui_counter).is_none() {
+                            ui_counter = 0;
+                        }
+                        let choice = Phase::from_index(ui_counter).unwrap_or(Phase::Preheat);
+                        ui::config_value_menu(
+                            &mut display,
+                            "TRACK BAND FOR",
+                            choice.name(),
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            track_phase = choice;
+                            let band = settings.tracking.bands[choice.index()];
+                            changed = true;
+                            // nothing checks above the cooling curve
+                            if choice == Phase::Cooling {
+                                ui_counter = settings.units.delta_to_counter(band.below, 100);
+                                config_state = ConfigSubMenus::TrackBelowEdit;
+                            } else {
+                                ui_counter = settings.units.delta_to_counter(band.above, 100);
+                                config_state = ConfigSubMenus::TrackAboveEdit;
+                            }
+                        }
+                    }
+                    ConfigSubMenus::TrackAboveEdit => {
+                        ui::config_number_menu(
+                            &mut display,
+                            "ABOVE CURVE",
+                            ui_counter as i32,
+                            settings.units.suffix(),
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.tracking.bands[track_phase.index()].above =
+                                settings.units.delta_from_counter(ui_counter, 100);
+                            ui_counter = settings.units.delta_to_counter(
+                                settings.tracking.bands[track_phase.index()].below,
+                                100,
+                            );
+                            changed = true;
+                            config_state = ConfigSubMenus::TrackBelowEdit;
+                        }
+                    }
+                    ConfigSubMenus::TrackBelowEdit => {
+                        ui::config_number_menu(
+                            &mut display,
+                            "BELOW CURVE",
+                            ui_counter as i32,
+                            settings.units.suffix(),
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.tracking.bands[track_phase.index()].below =
+                                settings.units.delta_from_counter(ui_counter, 100);
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::TrackWarnEdit => {
+                        ui::config_number_menu(
+                            &mut display,
+                            "TRACK WARN",
+                            ui_counter as i32,
+                            "S",
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.tracking.warn_after = ui_counter;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::TrackAbortEdit => {
+                        ui::config_number_menu(
+                            &mut display,
+                            "TRACK ABORT",
+                            ui_counter as i32,
+                            "S",
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.tracking.abort_after = ui_counter;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::BuzzerEdit => {
+                        let fitted = ui_counter % 2 == 1;
+                        ui::config_value_menu(
+                            &mut display,
+                            "BUZZER",
+                            if fitted { "FITTED" } else { "NONE" },
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.tracking.buzzer = fitted;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::RunawayRiseEdit => {
+                        ui::config_number_menu(
+                            &mut display,
+                            "RUNAWAY RISE",
+                            ui_counter as i32,
+                            settings.units.suffix(),
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.runaway.rise =
+                                settings.units.delta_from_counter(ui_counter, 100);
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::RunawayPeriodEdit => {
+                        // 0 would trip the moment the heater comes on
+                        ui_counter = ui_counter.max(1);
+                        ui::config_number_menu(
+                            &mut display,
+                            "RUNAWAY TIME",
+                            ui_counter as i32,
+                            "S",
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.runaway.period = ui_counter;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::RunawayMarginEdit => {
+                        ui::config_number_menu(
+                            &mut display,
+                            "RUNAWAY MARGIN",
+                            ui_counter as i32,
+                            settings.units.suffix(),
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.runaway.margin =
+                                settings.units.delta_from_counter(ui_counter, 100);
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::TuningRuleEdit => {
+                        if TuningRule::from_index(ui_counter).is_none() {
+                            ui_counter = 0;
+                        }
+                        let choice = TuningRule::from_index(ui_counter).unwrap_or_default();
+                        ui::config_value_menu(&mut display, "TUNE RULE", choice.name(), changed);
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            settings.tuning_rule = choice;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::AutotuneSetpointEdit => {
+                        let setpoint = settings
+                            .units
+                            .from_counter(ui_counter, settings.units.entry_step() * 2)
+                            .min(settings.max_temp);
+                        ui::config_number_menu(
+                            &mut display,
+                            "TUNE AT",
+                            settings.units.whole(setpoint),
+                            settings.units.suffix(),
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            ui_counter = 0;
+                            changed = true;
+                            match temp {
+                                Ok(_) => {
+                                    autotune = Some(Autotune::new(setpoint, now));
+                                    config_state = ConfigSubMenus::AutotuneRunning;
+                                }
+                                Err(why) => menu_fault = Some(Fault::Sensor(why)),
+                            }
+                        }
+                    }
+                    ConfigSubMenus::AutotuneRunning => {
+                        if let Some(tune) = &autotune {
+                            ui::autotune_menu(
+                                &mut display,
+                                temp.unwrap_or_default(),
+                                tune,
+                                settings.units,
+                                changed,
+                            );
+                        }
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            // heater goes off with the rest of the idle outputs
+                            autotune = None;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::AutotuneDone => {
+                        ui::autotune_done_menu(
+                            &mut display,
+                            autotune_result,
+                            &settings.pid,
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::Diagnostics => {
+                        // turn the knob to flip through the probes
+                        if ui_counter as usize >= NUM_SENSORS {
+                            ui_counter = 0;
+                        }
+                        let sensor = ui_counter as usize;
+                        ui::diagnostics_menu(
+                            &mut display,
+                            SENSOR_NAMES[sensor],
+                            raw_temps[sensor],
+                            temps[sensor],
+                            samplers[sensor].age(now).map(|ticks| ticks * 1000 / TICK_HZ),
+                            settings.units,
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::FaultLog => {
+                        // newest first, turn the knob to go further back
+                        let rst = ui::fault_log_menu(
+                            &mut display,
+                            ui_counter,
+                            faultlog::recent(ui_counter as u16),
+                            changed,
+                        );
+                        if !rst && ui_counter != 0 {
+                            ui_counter = 0;
+                        }
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                    ConfigSubMenus::CalibrateSensorSelect => {
+                        if ui_counter as usize >= NUM_SENSORS {
+                            ui_counter = 0;
+                        }
+                        ui::config_value_menu(
+                            &mut display,
+                            "CALIBRATE",
+                            SENSOR_NAMES[ui_counter as usize],
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            calibration_sensor = ui_counter as usize;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::CalibrateLow;
+                        }
+                    }
+                    ConfigSubMenus::CalibrateLow => {
+                        let raw_temp = raw_temps[calibration_sensor];
+                        // reference is dialled in, e.g. 0 for an ice bath
+                        let reference = settings
+                            .units
+                            .from_counter(ui_counter, settings.units.entry_step());
+                        ui::calibrate_menu(
+                            &mut display,
+                            "LOW",
+                            raw_temp,
+                            reference,
+                            settings.units,
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if let (true, Ok(raw)) = (button, raw_temp) {
+                            calibration_low = (raw, reference);
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::CalibrateHigh;
+                        }
+                    }
+                    ConfigSubMenus::CalibrateHigh => {
+                        let raw_temp = raw_temps[calibration_sensor];
+                        let reference = settings
+                            .units
+                            .from_counter(ui_counter, settings.units.entry_step());
+                        ui::calibrate_menu(
+                            &mut display,
+                            "HIGH",
+                            raw_temp,
+                            reference,
+                            settings.units,
+                            changed,
+                        );
+                        if changed {
+                            changed = false;
+                        }
+                        if let (true, Ok(raw)) = (button, raw_temp) {
+                            calibration_result =
+                                Calibration::from_two_points(calibration_low, (raw, reference));
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::CalibrateConfirm;
+                        }
+                    }
+                    ConfigSubMenus::CalibrateConfirm => {
+                        let rst = ui::calibrate_confirm_menu(
+                            &mut display,
+                            ui_counter,
+                            calibration_result,
+                            settings.units,
+                            changed,
+                        );
+                        if !rst {
+                            ui_counter = 0;
+                        }
+                        if changed {
+                            changed = false;
+                        }
+                        if button {
+                            // only takes effect for good, once the settings get saved
+                            if let (1, Some(calibration)) = (ui_counter, calibration_result) {
+                                settings.calibration[calibration_sensor] = calibration;
+                                filters[calibration_sensor].reset();
+                            }
+                            calibration_result = None;
+                            ui_counter = 0;
+                            changed = true;
+                            config_state = ConfigSubMenus::ConfigSelect;
+                        }
+                    }
+                },
+            }
+        }
+
+        // only while the timer is still ticking, it's what drives the relays
+        if now != last_kick {
+            watchdog::kick();
+            last_kick = now;
         }
 
         clocks += 1;
@@ -0,0 +1,264 @@
+use crate::phaseangle::{self, Channel};
+use crate::pwm::Window;
+use crate::zerocross;
+use crate::TICK_HZ;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicU16, AtomicU8, Ordering};
+use ruduino::Pin;
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputMode {
+    // slow PWM, works with anything down to a mechanical relay
+    #[default]
+    TimeProportional,
+    // whole mains half cycles, needs the zero-cross input
+    BurstFire,
+    // triac fired part way through every half cycle, also needs the zero-cross input
+    PhaseAngle,
+}
+
+impl OutputMode {
+    pub fn from_index(idx: u8) -> Option<OutputMode> {
+        match idx {
+            0 => Some(OutputMode::TimeProportional),
+            1 => Some(OutputMode::BurstFire),
+            2 => Some(OutputMode::PhaseAngle),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputMode::TimeProportional => "SLOW PWM",
+            OutputMode::BurstFire => "BURST FIRE",
+            OutputMode::PhaseAngle => "PHASE ANGLE",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputSettings {
+    pub mode: OutputMode,
+    // all in tenths of a second
+    pub period: u8,
+    // mechanical relays don't survive being clicked for a few ms at a time
+    pub min_on: u8,
+    pub min_off: u8,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        OutputSettings {
+            mode: OutputMode::TimeProportional,
+            period: 20,
+            min_on: 2,
+            min_off: 2,
+        }
+    }
+}
+
+const fn tenths_to_ticks(tenths: u8) -> u16 {
+    (tenths as u64 * TICK_HZ / 10) as u16
+}
+
+// lets outputs on different pins sit in one table
+pub trait Output {
+    fn configure(&self, settings: &OutputSettings);
+    fn set_duty(&self, duty: u8);
+    fn duty(&self) -> u8;
+    fn off(&self);
+}
+
+// slow PWM, turns a duty into on/off windows of a fixed period
+pub struct TimeProportional<P: Pin> {
+    // percent
+    duty: AtomicU8,
+    period: AtomicU16,
+    min_on: AtomicU16,
+    min_off: AtomicU16,
+    // only touched from the timer interrupt
+    elapsed: AtomicU16,
+    on_ticks: AtomicU16,
+    _pin: PhantomData<P>,
+}
+
+impl<P: Pin> TimeProportional<P> {
+    pub const fn new() -> Self {
+        TimeProportional {
+            duty: AtomicU8::new(0),
+            // real values come from configure() once settings are loaded
+            period: AtomicU16::new(tenths_to_ticks(20)),
+            min_on: AtomicU16::new(0),
+            min_off: AtomicU16::new(0),
+            elapsed: AtomicU16::new(0),
+            on_ticks: AtomicU16::new(0),
+            _pin: PhantomData,
+        }
+    }
+
+    pub fn configure(&self, settings: &OutputSettings) {
+        let window = Window::new(
+            tenths_to_ticks(settings.period),
+            tenths_to_ticks(settings.min_on),
+            tenths_to_ticks(settings.min_off),
+        );
+        self.period.store(window.period, Ordering::SeqCst);
+        self.min_on.store(window.min_on, Ordering::SeqCst);
+        self.min_off.store(window.min_off, Ordering::SeqCst);
+    }
+
+    // takes effect from the next window, except for 0 which is immediate
+    pub fn set_duty(&self, duty: u8) {
+        self.duty.store(duty.min(100), Ordering::SeqCst);
+    }
+
+    pub fn duty(&self) -> u8 {
+        self.duty.load(Ordering::SeqCst)
+    }
+
+    pub fn off(&self) {
+        self.set_duty(0);
+        P::set_low();
+    }
+
+    // call once a timer tick
+    pub fn tick(&self) {
+        let duty = self.duty.load(Ordering::SeqCst);
+        let period = self.period.load(Ordering::SeqCst);
+        let mut elapsed = self.elapsed.load(Ordering::SeqCst);
+
+        if elapsed >= period {
+            elapsed = 0;
+            // already clamped by configure()
+            let window = Window {
+                period,
+                min_on: self.min_on.load(Ordering::SeqCst),
+                min_off: self.min_off.load(Ordering::SeqCst),
+            };
+            self.on_ticks.store(window.on_ticks(duty), Ordering::SeqCst);
+        }
+
+        if duty != 0 && elapsed < self.on_ticks.load(Ordering::SeqCst) {
+            P::set_high();
+        } else {
+            P::set_low();
+        }
+        self.elapsed.store(elapsed + 1, Ordering::SeqCst);
+    }
+}
+
+impl<P: Pin> Output for TimeProportional<P> {
+    fn configure(&self, settings: &OutputSettings) {
+        TimeProportional::configure(self, settings)
+    }
+
+    fn set_duty(&self, duty: u8) {
+        TimeProportional::set_duty(self, duty)
+    }
+
+    fn duty(&self) -> u8 {
+        TimeProportional::duty(self)
+    }
+
+    fn off(&self) {
+        TimeProportional::off(self)
+    }
+}
+
+// heaters can also be switched on zero crossings, back to slow PWM whenever those stop
+pub struct HeaterOutput<P: Pin> {
+    slow: TimeProportional<P>,
+    // OutputMode as u8
+    mode: AtomicU8,
+    // carries the remainder over so on half cycles are spread out evenly
+    accumulator: AtomicU8,
+    // timer2 compare channel that fires the triac in phase angle mode
+    channel: Channel,
+}
+
+impl<P: Pin> HeaterOutput<P> {
+    pub const fn new(channel: Channel) -> Self {
+        HeaterOutput {
+            slow: TimeProportional::new(),
+            mode: AtomicU8::new(OutputMode::TimeProportional as u8),
+            accumulator: AtomicU8::new(0),
+            channel,
+        }
+    }
+
+    fn mode(&self) -> OutputMode {
+        OutputMode::from_index(self.mode.load(Ordering::SeqCst)).unwrap_or_default()
+    }
+
+    fn synced(&self) -> bool {
+        self.mode() != OutputMode::TimeProportional && zerocross::present()
+    }
+
+    // call once a timer tick
+    pub fn tick(&self) {
+        if !self.synced() {
+            self.slow.tick();
+        }
+    }
+
+    // call on every zero crossing, after phaseangle::start_half_cycle
+    pub fn cross(&self) {
+        // a compare still armed from the last half cycle would fire at the wrong point of this one
+        phaseangle::cancel(self.channel);
+        if !self.synced() {
+            return;
+        }
+        if self.mode() == OutputMode::PhaseAngle {
+            // the triac drops out by itself at the crossing, let go of the gate before refiring
+            P::set_low();
+            // the pulses can stop between synced() and here, don't guess at the half cycle
+            let frequency = match zerocross::frequency() {
+                Some(frequency) => frequency,
+                None => return,
+            };
+            match phaseangle::delay(self.slow.duty(), frequency) {
+                Some(0) => P::set_high(),
+                Some(counts) => phaseangle::schedule(self.channel, counts),
+                None => {}
+            }
+            return;
+        }
+        let mut accumulator = self.accumulator.load(Ordering::SeqCst) + self.slow.duty();
+        if accumulator >= 100 {
+            accumulator -= 100;
+            P::set_high();
+        } else {
+            P::set_low();
+        }
+        self.accumulator.store(accumulator, Ordering::SeqCst);
+    }
+
+    // call from the timer2 compare interrupt for this heater's channel
+    pub fn fire(&self) {
+        phaseangle::cancel(self.channel);
+        if self.synced() && self.slow.duty() != 0 {
+            P::set_high();
+        }
+    }
+}
+
+impl<P: Pin> Output for HeaterOutput<P> {
+    fn configure(&self, settings: &OutputSettings) {
+        self.slow.configure(settings);
+        self.mode.store(settings.mode as u8, Ordering::SeqCst);
+    }
+
+    fn set_duty(&self, duty: u8) {
+        self.slow.set_duty(duty)
+    }
+
+    fn duty(&self) -> u8 {
+        self.slow.duty()
+    }
+
+    fn off(&self) {
+        phaseangle::cancel(self.channel);
+        self.slow.off()
+    }
+}
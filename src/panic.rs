@@ -0,0 +1,110 @@
+use crate::eeprom;
+use crate::lcd::LCDHardware;
+use crate::watchdog;
+use crate::{heaters_off, FanRelay, FAN_OUTPUT};
+use ::lcd::Display;
+use avrd::current::{PCICR, TIMSK1, TIMSK2};
+use core::arch::asm;
+use core::fmt::{self, Formatter};
+use core::hint::spin_loop;
+use core::panic::PanicInfo;
+use core::ptr::write_volatile;
+use core::str::from_utf8;
+use ruduino::Pin;
+
+// the last 16 bytes of EEPROM, after the fault log
+const PANIC_ADDR: u16 = 0x3F0;
+// first byte says there's something there, then the line and as much of the file name as fits
+const PANIC_MARKER: u8 = b'P';
+const FILE_LEN: usize = 13;
+const LCD_WIDTH: usize = 16;
+
+pub struct PanicRecord {
+    pub line: u16,
+    file: [u8; FILE_LEN],
+}
+
+impl PanicRecord {
+    fn file(&self) -> &str {
+        let len = self.file.iter().position(|c| *c == 0).unwrap_or(FILE_LEN);
+        from_utf8(&self.file[..len]).unwrap_or("?")
+    }
+}
+
+// file:line, the name cut short to leave room for the number on one line of the LCD
+impl fmt::Display for PanicRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let digits = self.line.checked_ilog10().unwrap_or(0) as usize + 1;
+        let file = self.file();
+        let len = file.len().min(LCD_WIDTH - 1 - digits);
+        write!(f, "{}:{}", file.get(..len).unwrap_or("?"), self.line)
+    }
+}
+
+// the last panic, kept until someone has seen it and calls clear()
+pub fn last() -> Option<PanicRecord> {
+    if eeprom::read_byte(PANIC_ADDR) != PANIC_MARKER {
+        return None;
+    }
+    let mut line = [0_u8; 2];
+    let mut file = [0_u8; FILE_LEN];
+    eeprom::read_bytes(PANIC_ADDR + 1, &mut line);
+    eeprom::read_bytes(PANIC_ADDR + 3, &mut file);
+    Some(PanicRecord {
+        line: u16::from_le_bytes(line),
+        file,
+    })
+}
+
+pub fn clear() {
+    eeprom::write_byte(PANIC_ADDR, 0);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    unsafe { asm!("cli") }
+    // duties too, in case the timer gets to run again
+    heaters_off();
+    FAN_OUTPUT.set_duty(100);
+    FanRelay::set_high();
+    // the EEPROM and watchdog writes below turn interrupts back on when they're done,
+    // so switch off every interrupt source instead of relying on cli
+    unsafe {
+        write_volatile(TIMSK1, 0);
+        write_volatile(TIMSK2, 0);
+        write_volatile(PCICR, 0);
+    }
+
+    let (file, line) = match info.location() {
+        Some(location) => (
+            location.file().rsplit('/').next().unwrap_or(""),
+            location.line().min(u16::MAX as u32) as u16,
+        ),
+        None => ("", 0),
+    };
+    let mut record = PanicRecord {
+        line,
+        file: [0; FILE_LEN],
+    };
+    let len = file.len().min(FILE_LEN);
+    record.file[..len].copy_from_slice(&file.as_bytes()[..len]);
+
+    // saved before the screen, in case the screen is what's broken
+    watchdog::kick();
+    eeprom::write_bytes(PANIC_ADDR + 1, &record.line.to_le_bytes());
+    eeprom::write_bytes(PANIC_ADDR + 3, &record.file);
+    eeprom::write_byte(PANIC_ADDR, PANIC_MARKER);
+
+    // no unwrapping in here, there's nowhere left to go
+    let mut display = Display::new(LCDHardware {});
+    display.clear();
+    let _ = write!(display, "PANIC! HEAT OFF");
+    display.position(0, 1);
+    let _ = write!(display, "{}", record);
+
+    // make sure it's running, the reset picks the record up
+    watchdog::enable();
+    loop {
+        spin_loop();
+    }
+}
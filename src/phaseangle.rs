@@ -0,0 +1,92 @@
+use avrd::current::{OCR2A, OCR2B, TCCR2A, TCCR2B, TCNT2, TIFR2, TIMSK2};
+use core::ptr::{read_volatile, write_volatile};
+use ruduino::config::CPU_FREQUENCY_HZ;
+
+// timer2 runs freely at this rate, a mains half cycle is 130-156 counts
+const PRESCALER: u32 = 1024;
+const TCCR2B_PRESCALE_1024: u8 = 0b0000_0111;
+const OCF2A: u8 = 0b0000_0010;
+const OCF2B: u8 = 0b0000_0100;
+// anything later than this is too close to the next crossing to be worth firing
+const END_MARGIN: u8 = 4;
+
+// firing delay as a fraction of the half cycle out of 255, every 5% of power.
+// a resistive load gets 1 - a/pi + sin(2a)/(2pi) of full power when fired at angle a
+const LINEARISATION: [u8; 21] = [
+    255, 204, 189, 178, 169, 161, 154, 147, 140, 134, 127, 121, 115, 108, 101, 94, 86, 77, 66, 51,
+    0,
+];
+
+// one compare channel a heater, A for the bottom and B for the top
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Channel {
+    A,
+    B,
+}
+
+impl Channel {
+    // same bit in TIFR2 and TIMSK2
+    fn flag(&self) -> u8 {
+        match self {
+            Channel::A => OCF2A,
+            Channel::B => OCF2B,
+        }
+    }
+}
+
+pub fn setup() {
+    unsafe {
+        // normal mode, the compare interrupts are only switched on per half cycle
+        write_volatile(TCCR2A, 0);
+        write_volatile(TCCR2B, TCCR2B_PRESCALE_1024);
+        write_volatile(TIMSK2, 0);
+    }
+}
+
+// call on every zero crossing, before scheduling anything
+pub fn start_half_cycle() {
+    unsafe {
+        write_volatile(TCNT2, 0);
+    }
+}
+
+// timer2 counts from the crossing until the gate fires, None to leave it off this half cycle
+pub fn delay(duty: u8, frequency: u8) -> Option<u8> {
+    let duty = duty.min(100);
+    if duty == 0 {
+        return None;
+    }
+    let idx = (duty / 5) as usize;
+    let fraction = match LINEARISATION.get(idx + 1) {
+        Some(next) => {
+            let this = LINEARISATION[idx];
+            this - (this - next) * (duty % 5) / 5
+        }
+        None => LINEARISATION[idx],
+    };
+    let half_cycle = CPU_FREQUENCY_HZ as u32 / PRESCALER / (2 * frequency as u32);
+    let counts = (fraction as u32 * half_cycle / 255) as u8;
+    if counts + END_MARGIN >= half_cycle as u8 {
+        return None;
+    }
+    Some(counts)
+}
+
+pub fn schedule(channel: Channel, counts: u8) {
+    unsafe {
+        match channel {
+            Channel::A => write_volatile(OCR2A, counts),
+            Channel::B => write_volatile(OCR2B, counts),
+        }
+        // stale flag from the last half cycle would fire it straight away
+        write_volatile(TIFR2, channel.flag());
+        write_volatile(TIMSK2, read_volatile(TIMSK2) | channel.flag());
+    }
+}
+
+// one shot, call from the compare interrupt once the gate is fired
+pub fn cancel(channel: Channel) {
+    unsafe {
+        write_volatile(TIMSK2, read_volatile(TIMSK2) & !channel.flag());
+    }
+}
@@ -0,0 +1,154 @@
+use crate::celsius::Celsius;
+use serde::{Deserialize, Serialize};
+
+pub const OUTPUT_MAX: f32 = 100.0;
+
+// the config menu enters gains on a u8 counter in these steps, wide enough for tuned ovens
+pub const KP_STEP: f32 = 0.2;
+pub const KI_STEP: f32 = 0.005;
+pub const KD_STEP: f32 = 5.0;
+
+// output is heater duty in percent, so kp is %/C, ki is %/(C*s) and kd is %*s/C
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PidGains {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+}
+
+impl Default for PidGains {
+    fn default() -> Self {
+        PidGains {
+            kp: 5.0,
+            ki: 0.05,
+            kd: 40.0,
+        }
+    }
+}
+
+// no hardware in here so it can be run against a simulated oven
+#[derive(Default)]
+pub struct Pid {
+    // already multiplied by ki, so retuning doesn't bump the output
+    integral: f32,
+    last_measurement: Option<f32>,
+}
+
+impl Pid {
+    pub fn new() -> Pid {
+        Pid::default()
+    }
+
+    pub fn reset(&mut self) {
+        *self = Pid::default();
+    }
+
+    // dt in seconds, returns duty in percent. `feed_forward` is duty added on top,
+    // it counts towards saturation so the integral doesn't wind up fighting it
+    pub fn update(
+        &mut self,
+        gains: &PidGains,
+        setpoint: Celsius,
+        measurement: Celsius,
+        feed_forward: f32,
+        dt: f32,
+    ) -> f32 {
+        let setpoint = setpoint.0 as f32 / 100.0;
+        let measurement = measurement.0 as f32 / 100.0;
+        let error = setpoint - measurement;
+
+        let proportional = gains.kp * error;
+        // derivative on measurement, setpoint steps between segments don't kick the output
+        let derivative = match self.last_measurement {
+            Some(last) if dt > 0.0 => -gains.kd * (measurement - last) / dt,
+            _ => 0.0,
+        };
+        self.last_measurement = Some(measurement);
+
+        // anti-windup, stop integrating once the output is pinned in the same direction
+        let integral = self.integral + gains.ki * error * dt;
+        let unclamped = proportional + integral + derivative + feed_forward;
+        let saturated_high = unclamped > OUTPUT_MAX && error > 0.0;
+        let saturated_low = unclamped < 0.0 && error < 0.0;
+        if !saturated_high && !saturated_low {
+            self.integral = integral.clamp(0.0, OUTPUT_MAX);
+        }
+
+        (proportional + self.integral + derivative + feed_forward).clamp(0.0, OUTPUT_MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AMBIENT: f32 = 25.0;
+    // first order oven, degrees above ambient at full duty and the time constant in seconds
+    const PLANT_GAIN: f32 = 300.0;
+    const PLANT_TAU: f32 = 240.0;
+    const DT: f32 = 1.0;
+
+    fn plant(temp: f32, duty: f32) -> f32 {
+        temp + (AMBIENT + PLANT_GAIN * duty / OUTPUT_MAX - temp) * DT / PLANT_TAU
+    }
+
+    fn celsius(temp: f32) -> Celsius {
+        Celsius((temp * 100.0) as i32)
+    }
+
+    // returns the temperature at the end
+    fn run(pid: &mut Pid, setpoint: f32, mut temp: f32, seconds: u32) -> f32 {
+        let gains = PidGains::default();
+        for _ in 0..seconds {
+            let duty = pid.update(&gains, celsius(setpoint), celsius(temp), 0.0, DT);
+            assert!((0.0..=OUTPUT_MAX).contains(&duty));
+            temp = plant(temp, duty);
+        }
+        temp
+    }
+
+    #[test]
+    fn settles_on_the_setpoint() {
+        let mut pid = Pid::new();
+        let temp = run(&mut pid, 150.0, AMBIENT, 1800);
+        assert!((temp - 150.0).abs() < 0.5, "ended at {}", temp);
+    }
+
+    #[test]
+    fn output_is_clamped() {
+        let gains = PidGains::default();
+        let mut pid = Pid::new();
+        let full = pid.update(&gains, celsius(1000.0), celsius(AMBIENT), 0.0, DT);
+        assert_eq!(full, OUTPUT_MAX);
+        let mut pid = Pid::new();
+        let off = pid.update(&gains, celsius(AMBIENT), celsius(300.0), 0.0, DT);
+        assert_eq!(off, 0.0);
+    }
+
+    #[test]
+    fn no_windup_while_saturated() {
+        let mut pid = Pid::new();
+        // out of reach, the output sits at full the whole time
+        let temp = run(&mut pid, 500.0, AMBIENT, 1800);
+        assert!(pid.integral < 10.0, "integral wound up to {}", pid.integral);
+
+        // so dropping the setpoint cuts the heat straight away
+        let gains = PidGains::default();
+        let duty = pid.update(&gains, celsius(150.0), celsius(temp), 0.0, DT);
+        assert_eq!(duty, 0.0);
+        let temp = run(&mut pid, 150.0, temp, 1800);
+        assert!((temp - 150.0).abs() < 0.5, "ended at {}", temp);
+    }
+
+    #[test]
+    fn feed_forward_counts_towards_saturation() {
+        let gains = PidGains::default();
+        let mut pid = Pid::new();
+        // the feed-forward alone nearly fills the output, the integral has nothing to add
+        for _ in 0..100 {
+            let duty = pid.update(&gains, celsius(150.0), celsius(140.0), 80.0, DT);
+            assert_eq!(duty, OUTPUT_MAX);
+        }
+        assert_eq!(pid.integral, 0.0);
+    }
+}
@@ -1,5 +1,4 @@
-use alloc::string::String;
-use alloc::vec::Vec;
+use crate::celsius::Celsius;
 use serde::{Deserialize, Serialize};
 
 #[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
@@ -10,13 +9,149 @@ pub struct Profile {
 
 #[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct CurvePoint {
-    pub temp: u16,
+    pub temp: Celsius,
     pub time_seconds: u16,
-    pub disabled: bool
+    pub disabled: bool,
+    pub advance: Advance,
+    // seconds to wait for the temperature past time_seconds, 0 waits forever
+    pub timeout: u16
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Serialize, Deserialize)]
+// what has to happen before the run moves past a point
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum Advance {
+    #[default]
+    Time,
+    Temp,
+    Either,
+    Both
+}
+
+impl Advance {
+    pub fn from_index(idx: u8) -> Option<Advance> {
+        match idx {
+            0 => Some(Advance::Time),
+            1 => Some(Advance::Temp),
+            2 => Some(Advance::Either),
+            3 => Some(Advance::Both),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Advance::Time => "TIME",
+            Advance::Temp => "TEMP",
+            Advance::Either => "EITHER",
+            Advance::Both => "BOTH"
+        }
+    }
+
+    // held at the point until the temperature is there
+    pub fn waits(&self) -> bool {
+        matches!(self, Advance::Temp | Advance::Both)
+    }
+
+    // moves on early once the temperature is there
+    pub fn skips(&self) -> bool {
+        matches!(self, Advance::Temp | Advance::Either)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Profiles {
     pub num_profiles: u8,
     pub profiles: [Option<Profile>; 16]
+}
+
+// in EEPROM the length goes first, big end first, then the format and then the blob,
+// all of it below the settings
+pub const PROFILES_HEADER_LEN: u16 = 3;
+pub const PROFILES_MAX_LEN: usize = 0x320 - PROFILES_HEADER_LEN as usize;
+const PROFILES_VERSION: u8 = 1;
+// point timeouts are entered and stored in steps of this many seconds
+pub const TIMEOUT_STEP: u16 = 5;
+
+// whole degrees and the flags in one byte, sixteen full profiles have to fit
+#[derive(Copy, Clone, Serialize, Deserialize)]
+struct StoredPoint {
+    temp: u16,
+    time_seconds: u16,
+    // bit 0 disabled, bits 1 and 2 the advance
+    flags: u8,
+    // in TIMEOUT_STEPs
+    timeout: u8
+}
+
+// from before there was a format byte
+#[derive(Copy, Clone, Serialize, Deserialize)]
+struct LegacyPoint {
+    temp: u16,
+    time_seconds: u16,
+    disabled: bool
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+struct StoredProfile<P> {
+    name: [u8; 6],
+    points: [P; 6]
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+struct StoredProfiles<P> {
+    num_profiles: u8,
+    profiles: [Option<StoredProfile<P>>; 16]
+}
+
+impl From<StoredPoint> for CurvePoint {
+    fn from(point: StoredPoint) -> CurvePoint {
+        CurvePoint {
+            temp: Celsius::from_degrees(point.temp as i32),
+            time_seconds: point.time_seconds,
+            disabled: point.flags & 1 != 0,
+            advance: Advance::from_index((point.flags >> 1) & 0b11).unwrap_or_default(),
+            timeout: point.timeout as u16 * TIMEOUT_STEP
+        }
+    }
+}
+
+impl From<LegacyPoint> for CurvePoint {
+    fn from(point: LegacyPoint) -> CurvePoint {
+        CurvePoint {
+            temp: Celsius::from_degrees(point.temp as i32),
+            time_seconds: point.time_seconds,
+            disabled: point.disabled,
+            ..CurvePoint::default()
+        }
+    }
+}
+
+impl<P: Copy + Into<CurvePoint>> From<StoredProfiles<P>> for Profiles {
+    fn from(stored: StoredProfiles<P>) -> Profiles {
+        Profiles {
+            num_profiles: stored.num_profiles,
+            profiles: stored.profiles.map(|profile| {
+                profile.map(|profile| Profile {
+                    name: profile.name,
+                    points: profile.points.map(Into::into)
+                })
+            })
+        }
+    }
+}
+
+impl Profiles {
+    // anything that doesn't decode is dropped, better no profiles than wrong ones
+    pub fn decode(version: u8, data: &[u8]) -> Profiles {
+        if data.len() > PROFILES_MAX_LEN {
+            return Profiles::default();
+        }
+        let decoded = match version {
+            PROFILES_VERSION => {
+                postcard::from_bytes::<StoredProfiles<StoredPoint>>(data).map(Profiles::from)
+            }
+            _ => postcard::from_bytes::<StoredProfiles<LegacyPoint>>(data).map(Profiles::from)
+        };
+        decoded.unwrap_or_default()
+    }
 }
\ No newline at end of file
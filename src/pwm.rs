@@ -0,0 +1,55 @@
+// one slow PWM window, everything in timer ticks
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Window {
+    pub period: u16,
+    pub min_on: u16,
+    pub min_off: u16,
+}
+
+impl Window {
+    // a minimum longer than the window could never be met and would hold the output off
+    pub fn new(period: u16, min_on: u16, min_off: u16) -> Window {
+        let period = period.max(1);
+        Window {
+            period,
+            min_on: min_on.min(period),
+            min_off: min_off.min(period),
+        }
+    }
+
+    // duty in percent
+    pub fn on_ticks(&self, duty: u8) -> u16 {
+        let on_ticks = (duty.min(100) as u32 * self.period as u32 / 100) as u16;
+        // too short to be worth switching for, round to fully off or fully on
+        if on_ticks < self.min_on {
+            0
+        } else if self.period - on_ticks < self.min_off {
+            self.period
+        } else {
+            on_ticks
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_short_switches() {
+        let window = Window::new(200, 20, 20);
+        assert_eq!(window.on_ticks(0), 0);
+        assert_eq!(window.on_ticks(5), 0);
+        assert_eq!(window.on_ticks(50), 100);
+        assert_eq!(window.on_ticks(95), 200);
+        assert_eq!(window.on_ticks(100), 200);
+    }
+
+    #[test]
+    fn minimum_longer_than_the_period() {
+        let window = Window::new(100, 250, 250);
+        assert_eq!(window.min_on, 100);
+        assert_eq!(window.on_ticks(100), 100);
+        assert_eq!(window.on_ticks(0), 0);
+    }
+}
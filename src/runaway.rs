@@ -0,0 +1,93 @@
+use crate::temperature::Celsius;
+use crate::TICK_HZ;
+use serde::{Deserialize, Serialize};
+
+// heater duty that counts as trying hard to heat, below this the oven may just be holding
+const HEATING_DUTY: u8 = 90;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunawaySettings {
+    // flat out, the oven has to rise by this much every `period` seconds
+    pub rise: Celsius,
+    pub period: u8,
+    // with the heater off, how far it may climb from the coolest point since
+    pub margin: Celsius,
+}
+
+impl Default for RunawaySettings {
+    fn default() -> Self {
+        RunawaySettings {
+            rise: Celsius::from_degrees(3),
+            period: 60,
+            margin: Celsius::from_degrees(25),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Runaway {
+    // element dead, thermocouple fallen out of the oven
+    NoRise,
+    // welded relay or a shorted SSR
+    HeatingWhileOff,
+}
+
+impl Runaway {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Runaway::NoRise => "NOT HEATING",
+            Runaway::HeatingWhileOff => "HEATING WHEN OFF",
+        }
+    }
+}
+
+// checks what the heater is told to do against what the temperature does
+#[derive(Default)]
+pub struct RunawayMonitor {
+    // temperature and tick the current full power stretch was last on track at
+    heating_from: Option<(Celsius, u64)>,
+    // coolest seen since the heater was commanded off
+    off_low: Option<Celsius>,
+}
+
+impl RunawayMonitor {
+    pub fn new() -> RunawayMonitor {
+        RunawayMonitor::default()
+    }
+
+    // `duty` is the highest commanded to any heater, `now` in timer ticks.
+    // runs whether or not a profile is, a welded relay heats an idle oven too
+    pub fn update(
+        &mut self,
+        settings: &RunawaySettings,
+        duty: u8,
+        temp: Celsius,
+        now: u64,
+    ) -> Result<(), Runaway> {
+        if duty >= HEATING_DUTY {
+            match self.heating_from {
+                Some((from, _)) if temp >= from + settings.rise => {
+                    self.heating_from = Some((temp, now));
+                }
+                Some((_, since)) if now - since >= settings.period as u64 * TICK_HZ => {
+                    return Err(Runaway::NoRise);
+                }
+                Some(_) => {}
+                None => self.heating_from = Some((temp, now)),
+            }
+        } else {
+            self.heating_from = None;
+        }
+
+        if duty == 0 {
+            let low = self.off_low.get_or_insert(temp);
+            *low = (*low).min(temp);
+            if temp > *low + settings.margin {
+                return Err(Runaway::HeatingWhileOff);
+            }
+        } else {
+            self.off_low = None;
+        }
+        Ok(())
+    }
+}
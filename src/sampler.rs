@@ -0,0 +1,74 @@
+use crate::temperature::{Celsius, Reading, TemperatureError};
+use crate::TICK_HZ;
+
+// MAX6675 worst case is 220ms, pulling CS low any earlier aborts the conversion
+pub const CONVERSION_TICKS: u64 = TICK_HZ / 4;
+// a few missed conversions in a row and we stop trusting the last one
+pub const STALE_TICKS: u64 = CONVERSION_TICKS * 4;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sample {
+    pub reading: Reading,
+    pub taken_at: u64,
+}
+
+pub struct Sampler {
+    read: fn() -> Result<Reading, TemperatureError>,
+    last_read: u64,
+    latest: Option<Sample>,
+    fault: Option<TemperatureError>,
+}
+
+impl Sampler {
+    pub fn new(read: fn() -> Result<Reading, TemperatureError>) -> Sampler {
+        Sampler {
+            read,
+            last_read: 0,
+            latest: None,
+            fault: None,
+        }
+    }
+
+    // returns true when a new conversion was read out
+    pub fn poll(&mut self, now: u64) -> bool {
+        // every read restarts the conversion, so this holds even before the first good one
+        if now - self.last_read < CONVERSION_TICKS {
+            return false;
+        }
+        self.last_read = now;
+        match (self.read)() {
+            Ok(reading) => {
+                self.latest = Some(Sample {
+                    reading,
+                    taken_at: now,
+                });
+                self.fault = None;
+            }
+            Err(why) => self.fault = Some(why),
+        }
+        true
+    }
+
+    pub fn latest(&self) -> Option<Sample> {
+        self.latest
+    }
+
+    // ticks since the last good sample
+    pub fn age(&self, now: u64) -> Option<u64> {
+        self.latest.map(|s| now - s.taken_at)
+    }
+
+    pub fn temperature(&self, now: u64) -> Result<Celsius, TemperatureError> {
+        if let Some(why) = self.fault {
+            return Err(why);
+        }
+        match (self.latest, self.age(now)) {
+            (Some(sample), Some(age)) if age <= STALE_TICKS => Ok(sample.reading.hot_junction),
+            _ => Err(TemperatureError::Stale),
+        }
+    }
+
+    pub fn cold_junction(&self) -> Option<Celsius> {
+        self.latest.and_then(|s| s.reading.cold_junction)
+    }
+}
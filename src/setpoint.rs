@@ -0,0 +1,455 @@
+use crate::celsius::Celsius;
+use crate::profile::{CurvePoint, Profile};
+use serde::{Deserialize, Serialize};
+
+// anything climbing slower than this is a soak, in hundredths of a degree a second
+const SOAK_SLOPE: i32 = 50;
+// a point that waits for its temperature counts it as reached this close
+const REACHED_BAND: Celsius = Celsius(200);
+
+// the elements keep heating well after the relay opens, so steer by where the curve is going
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LookaheadSettings {
+    // seconds
+    pub time: u8,
+    // duty in percent for every C/s the curve is climbing
+    pub feed_forward: u8,
+}
+
+impl Default for LookaheadSettings {
+    fn default() -> Self {
+        LookaheadSettings {
+            time: 10,
+            feed_forward: 10,
+        }
+    }
+}
+
+pub const NUM_PHASES: usize = 4;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Preheat,
+    Soak,
+    Reflow,
+    Cooling,
+}
+
+impl Phase {
+    pub fn from_index(idx: u8) -> Option<Phase> {
+        match idx {
+            0 => Some(Phase::Preheat),
+            1 => Some(Phase::Soak),
+            2 => Some(Phase::Reflow),
+            3 => Some(Phase::Cooling),
+            _ => None,
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Preheat => "PREHEAT",
+            Phase::Soak => "SOAK",
+            Phase::Reflow => "REFLOW",
+            Phase::Cooling => "COOLING",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Setpoint {
+    // already looking ahead
+    pub target: Celsius,
+    // where the curve is right now, for checking the oven is keeping up
+    pub current: Celsius,
+    // per second, of the curve at the lookahead time
+    pub slope: Celsius,
+    // index of the point we are heading towards, now rather than ahead
+    pub segment: usize,
+    pub phase: Phase,
+}
+
+impl Setpoint {
+    // duty in percent to add on top of the controller, negative on the way down
+    pub fn feed_forward(&self, lookahead: &LookaheadSettings) -> f32 {
+        lookahead.feed_forward as f32 * self.slope.0 as f32 / 100.0
+    }
+}
+
+// where the oven should be `elapsed` seconds into a run, None once the profile is done.
+// `start` is the oven temperature when the run began, the first point is ramped to from there
+pub fn setpoint(
+    profile: &Profile,
+    start: Celsius,
+    elapsed: u32,
+    lookahead: &LookaheadSettings,
+) -> Option<Setpoint> {
+    let peak = peak(profile)?;
+    let now = locate(profile, start, elapsed)?;
+    let ahead = elapsed + lookahead.time as u32;
+    // past the end the curve just stays where the last point left it
+    let (target, slope) = match locate(profile, start, ahead) {
+        Some(seg) => (
+            interpolate(&seg.from, &seg.to, ahead),
+            slope(&seg.from, &seg.to),
+        ),
+        None => (last(profile)?, Celsius::default()),
+    };
+    Some(Setpoint {
+        target,
+        current: interpolate(&now.from, &now.to, elapsed),
+        slope,
+        segment: now.to_idx,
+        phase: phase(&now.from, &now.to, peak),
+    })
+}
+
+struct Segment {
+    from: CurvePoint,
+    // None while still ramping from the starting temperature
+    from_idx: Option<usize>,
+    to: CurvePoint,
+    to_idx: usize,
+}
+
+// the segment we're in at `elapsed`
+fn locate(profile: &Profile, start: Celsius, elapsed: u32) -> Option<Segment> {
+    let mut from = CurvePoint {
+        temp: start,
+        ..CurvePoint::default()
+    };
+    let mut from_idx = None;
+    for (to_idx, to) in profile
+        .points
+        .iter()
+        .enumerate()
+        .filter(|(_, point)| !point.disabled)
+    {
+        // points that go back in time are passed over as soon as we reach them,
+        // so from.time_seconds <= elapsed < to.time_seconds in here
+        if elapsed < to.time_seconds as u32 {
+            return Some(Segment {
+                from,
+                from_idx,
+                to: *to,
+                to_idx,
+            });
+        }
+        from = *to;
+        from_idx = Some(to_idx);
+    }
+    None
+}
+
+// the curve's own time, it runs with the wall clock except where a point
+// holds it back waiting for the temperature or lets it skip ahead
+#[derive(Default)]
+pub struct Clock {
+    // seconds, negative once we've waited somewhere
+    offset: i64,
+    // last point whose temperature was reached, it's not waited on again
+    cleared: Option<usize>,
+    // wall clock seconds the current wait started
+    waiting_since: Option<u64>,
+}
+
+impl Clock {
+    pub fn new() -> Clock {
+        Clock::default()
+    }
+
+    pub fn curve_time(&self, elapsed: u64) -> u32 {
+        (elapsed as i64 + self.offset).max(0) as u32
+    }
+
+    pub fn waiting(&self) -> bool {
+        self.waiting_since.is_some()
+    }
+
+    // `elapsed` is wall clock seconds into the run, Err is the index of a point that timed out
+    pub fn update(
+        &mut self,
+        profile: &Profile,
+        start: Celsius,
+        elapsed: u64,
+        temp: Celsius,
+    ) -> Result<(), u8> {
+        let now = self.curve_time(elapsed);
+        let seg = match locate(profile, start, now) {
+            Some(seg) => seg,
+            None => return Ok(()),
+        };
+
+        // just got to a point that wants its temperature first
+        if let Some(from_idx) = seg.from_idx {
+            if seg.from.advance.waits() && self.cleared != Some(from_idx) {
+                if (temp - seg.from.temp).0.abs() <= REACHED_BAND.0 {
+                    self.cleared = Some(from_idx);
+                    self.waiting_since = None;
+                } else {
+                    // pin the curve to the point until it's reached
+                    self.offset = seg.from.time_seconds as i64 - elapsed as i64;
+                    let since = *self.waiting_since.get_or_insert(elapsed);
+                    if seg.from.timeout != 0 && elapsed - since > seg.from.timeout as u64 {
+                        return Err(from_idx as u8);
+                    }
+                    return Ok(());
+                }
+            }
+        }
+
+        // or the next one is already there, no need to wait out the clock
+        if seg.to.advance.skips() && reached(&seg.from, &seg.to, temp) {
+            self.offset += seg.to.time_seconds as i64 - now as i64;
+            self.cleared = Some(seg.to_idx);
+        }
+        Ok(())
+    }
+}
+
+// past the point in the direction the segment is going
+fn reached(from: &CurvePoint, to: &CurvePoint, temp: Celsius) -> bool {
+    if to.temp >= from.temp {
+        temp >= to.temp - REACHED_BAND
+    } else {
+        temp <= to.temp + REACHED_BAND
+    }
+}
+
+// length of the whole run in seconds
+pub fn duration(profile: &Profile) -> u16 {
+    profile
+        .points
+        .iter()
+        .filter(|point| !point.disabled)
+        .map(|point| point.time_seconds)
+        .max()
+        .unwrap_or(0)
+}
+
+// hottest enabled point
+pub fn peak(profile: &Profile) -> Option<Celsius> {
+    profile
+        .points
+        .iter()
+        .filter(|point| !point.disabled)
+        .map(|point| point.temp)
+        .max()
+}
+
+fn last(profile: &Profile) -> Option<Celsius> {
+    profile
+        .points
+        .iter()
+        .rev()
+        .find(|point| !point.disabled)
+        .map(|point| point.temp)
+}
+
+fn slope(from: &CurvePoint, to: &CurvePoint) -> Celsius {
+    let span = to.time_seconds.saturating_sub(from.time_seconds).max(1) as i32;
+    (to.temp - from.temp) / span
+}
+
+fn interpolate(from: &CurvePoint, to: &CurvePoint, elapsed: u32) -> Celsius {
+    let span = to.time_seconds as i64 - from.time_seconds as i64;
+    let into = elapsed as i64 - from.time_seconds as i64;
+    if span <= 0 {
+        return to.temp;
+    }
+    // i64, a full scale swing over a long segment doesn't fit in an i32
+    let delta = (to.temp - from.temp).0 as i64;
+    from.temp + Celsius((delta * into / span) as i32)
+}
+
+fn phase(from: &CurvePoint, to: &CurvePoint, peak: Celsius) -> Phase {
+    if to.temp < from.temp {
+        return Phase::Cooling;
+    }
+    if to.temp == peak {
+        return Phase::Reflow;
+    }
+    if slope(from, to).0 < SOAK_SLOPE {
+        Phase::Soak
+    } else {
+        Phase::Preheat
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::Advance;
+
+    const NO_LOOKAHEAD: LookaheadSettings = LookaheadSettings {
+        time: 0,
+        feed_forward: 0,
+    };
+
+    fn point(degrees: i32, time_seconds: u16) -> CurvePoint {
+        CurvePoint {
+            temp: Celsius::from_degrees(degrees),
+            time_seconds,
+            ..CurvePoint::default()
+        }
+    }
+
+    fn profile(points: [CurvePoint; 6]) -> Profile {
+        Profile {
+            name: *b"TEST  ",
+            points,
+        }
+    }
+
+    // preheat, soak, reflow and cooling, the last two points unused
+    fn reflow() -> Profile {
+        let mut points = [
+            point(150, 60),
+            point(180, 150),
+            point(240, 210),
+            point(100, 300),
+            point(0, 0),
+            point(0, 0),
+        ];
+        points[4].disabled = true;
+        points[5].disabled = true;
+        profile(points)
+    }
+
+    #[test]
+    fn ramps_from_the_start_temperature() {
+        let profile = reflow();
+        let now = setpoint(&profile, Celsius::from_degrees(30), 30, &NO_LOOKAHEAD).unwrap();
+        assert_eq!(now.current, Celsius::from_degrees(90));
+        assert_eq!(now.target, now.current);
+        assert_eq!(now.segment, 0);
+    }
+
+    #[test]
+    fn reports_segment_and_phase() {
+        let profile = reflow();
+        let start = Celsius::from_degrees(30);
+        let at = |elapsed| setpoint(&profile, start, elapsed, &NO_LOOKAHEAD).unwrap();
+        assert_eq!((at(10).segment, at(10).phase), (0, Phase::Preheat));
+        assert_eq!((at(100).segment, at(100).phase), (1, Phase::Soak));
+        assert_eq!((at(180).segment, at(180).phase), (2, Phase::Reflow));
+        assert_eq!((at(250).segment, at(250).phase), (3, Phase::Cooling));
+    }
+
+    #[test]
+    fn skips_disabled_points() {
+        let mut profile = reflow();
+        // the soak point no longer counts, preheat runs straight into reflow
+        profile.points[1].disabled = true;
+        let now = setpoint(&profile, Celsius::from_degrees(30), 135, &NO_LOOKAHEAD).unwrap();
+        assert_eq!(now.current, Celsius::from_degrees(195));
+        assert_eq!(now.segment, 2);
+        assert_eq!(peak(&profile), Some(Celsius::from_degrees(240)));
+    }
+
+    #[test]
+    fn descends_without_underflow() {
+        let profile = reflow();
+        // hotter than the first point, the curve comes down to it
+        let now = setpoint(&profile, Celsius::from_degrees(250), 30, &NO_LOOKAHEAD).unwrap();
+        assert_eq!(now.current, Celsius::from_degrees(200));
+        assert_eq!(now.phase, Phase::Cooling);
+        assert!(now.slope < Celsius::default());
+
+        let cooling = setpoint(&profile, Celsius::from_degrees(30), 255, &NO_LOOKAHEAD).unwrap();
+        assert_eq!(cooling.current, Celsius::from_degrees(170));
+        assert_eq!(cooling.slope, Celsius(-155));
+    }
+
+    #[test]
+    fn ends_with_the_profile() {
+        let profile = reflow();
+        let start = Celsius::from_degrees(30);
+        assert_eq!(duration(&profile), 300);
+        assert!(setpoint(&profile, start, 300, &NO_LOOKAHEAD).is_none());
+
+        // looking past the end holds the last point
+        let lookahead = LookaheadSettings {
+            time: 60,
+            feed_forward: 10,
+        };
+        let near_end = setpoint(&profile, start, 280, &lookahead).unwrap();
+        assert_eq!(near_end.target, Celsius::from_degrees(100));
+        assert_eq!(near_end.slope, Celsius::default());
+        assert_eq!(near_end.feed_forward(&lookahead), 0.0);
+    }
+
+    #[test]
+    fn waits_for_the_temperature() {
+        for advance in [Advance::Temp, Advance::Both] {
+            let mut profile = reflow();
+            profile.points[0].advance = advance;
+            let start = Celsius::from_degrees(30);
+            let mut clock = Clock::new();
+            // the oven is slow, the curve stays pinned to the point
+            let slow = Celsius::from_degrees(120);
+            for elapsed in 60..90 {
+                assert_eq!(clock.update(&profile, start, elapsed, slow), Ok(()));
+            }
+            assert!(clock.waiting());
+            assert_eq!(clock.curve_time(89), 60);
+
+            // and runs on from it once the temperature is there
+            let there = Celsius::from_degrees(149);
+            assert_eq!(clock.update(&profile, start, 90, there), Ok(()));
+            assert!(!clock.waiting());
+            assert_eq!(clock.curve_time(90), 61);
+        }
+    }
+
+    #[test]
+    fn skips_ahead_once_reached() {
+        for advance in [Advance::Either, Advance::Temp] {
+            let mut profile = reflow();
+            profile.points[1].advance = advance;
+            let start = Celsius::from_degrees(30);
+            let mut clock = Clock::new();
+            let there = Celsius::from_degrees(179);
+            assert_eq!(clock.update(&profile, start, 100, there), Ok(()));
+            assert_eq!(clock.curve_time(100), 150);
+
+            // already reached, a point that also waits doesn't hold the curve again
+            let dipped = Celsius::from_degrees(175);
+            assert_eq!(clock.update(&profile, start, 101, dipped), Ok(()));
+            assert!(!clock.waiting());
+            assert_eq!(clock.curve_time(101), 151);
+        }
+    }
+
+    #[test]
+    fn times_out_waiting() {
+        let mut profile = reflow();
+        profile.points[1].advance = Advance::Temp;
+        profile.points[1].timeout = 30;
+        let start = Celsius::from_degrees(30);
+        let cold = Celsius::from_degrees(100);
+        let mut clock = Clock::new();
+        assert_eq!(clock.update(&profile, start, 150, cold), Ok(()));
+        assert_eq!(clock.update(&profile, start, 180, cold), Ok(()));
+        assert_eq!(clock.update(&profile, start, 181, cold), Err(1));
+    }
+
+    #[test]
+    fn no_timeout_waits_forever() {
+        let mut profile = reflow();
+        profile.points[1].advance = Advance::Both;
+        let start = Celsius::from_degrees(30);
+        let cold = Celsius::from_degrees(100);
+        let mut clock = Clock::new();
+        // however long it's left, the curve never moves past the point
+        for elapsed in 150..1000 {
+            assert_eq!(clock.update(&profile, start, elapsed, cold), Ok(()));
+        }
+        assert!(clock.waiting());
+        assert_eq!(clock.curve_time(999), 150);
+    }
+}
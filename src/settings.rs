@@ -0,0 +1,91 @@
+use crate::autotune::TuningRule;
+use crate::calibration::Calibration;
+use crate::cooling::CoolingSettings;
+use crate::eeprom;
+use crate::filter::FilterSettings;
+use crate::heater::HeaterSettings;
+use crate::output::OutputSettings;
+use crate::pid::PidGains;
+use crate::runaway::RunawaySettings;
+use crate::setpoint::LookaheadSettings;
+use crate::temperature::{
+    Averaging, Celsius, ControlSource, MainsFilter, NtcSettings, ThermocoupleType, Units,
+    NUM_SENSORS,
+};
+use crate::tracking::TrackingSettings;
+use serde::{Deserialize, Serialize};
+
+// the profiles blob lives below this, see PROFILES_MAX_LEN
+const SETTINGS_ADDR: u16 = 0x320;
+const SETTINGS_MAX_LEN: usize = 158;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    pub thermocouple_type: ThermocoupleType,
+    pub averaging: Averaging,
+    pub mains_filter: MainsFilter,
+    pub filter: FilterSettings,
+    pub calibration: [Calibration; NUM_SENSORS],
+    pub control_source: ControlSource,
+    pub ntc: NtcSettings,
+    pub units: Units,
+    pub pid: PidGains,
+    pub heater_output: OutputSettings,
+    pub tuning_rule: TuningRule,
+    pub lookahead: LookaheadSettings,
+    pub heaters: HeaterSettings,
+    pub cooling: CoolingSettings,
+    pub tracking: TrackingSettings,
+    pub runaway: RunawaySettings,
+    // nothing heats past this, whatever a profile says
+    pub max_temp: Celsius,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            thermocouple_type: ThermocoupleType::default(),
+            averaging: Averaging::default(),
+            mains_filter: MainsFilter::default(),
+            filter: FilterSettings::default(),
+            calibration: [Calibration::default(); NUM_SENSORS],
+            control_source: ControlSource::default(),
+            ntc: NtcSettings::default(),
+            units: Units::default(),
+            pid: PidGains::default(),
+            heater_output: OutputSettings::default(),
+            tuning_rule: TuningRule::default(),
+            lookahead: LookaheadSettings::default(),
+            heaters: HeaterSettings::default(),
+            cooling: CoolingSettings::default(),
+            tracking: TrackingSettings::default(),
+            runaway: RunawaySettings::default(),
+            max_temp: Celsius::from_degrees(260),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Settings {
+        let mut len = [0_u8; 2];
+        eeprom::read_bytes(SETTINGS_ADDR, &mut len);
+        let len = u16::from_le_bytes(len) as usize;
+        // blank EEPROM reads back 0xFF
+        if len == 0 || len > SETTINGS_MAX_LEN {
+            return Settings::default();
+        }
+
+        let mut data = [0_u8; SETTINGS_MAX_LEN];
+        eeprom::read_bytes(SETTINGS_ADDR + 2, &mut data[..len]);
+        postcard::from_bytes::<Settings>(&data[..len]).unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        let mut data = [0_u8; SETTINGS_MAX_LEN];
+        if let Ok(used) = postcard::to_slice(self, &mut data) {
+            let len = used.len() as u16;
+            eeprom::write_bytes(SETTINGS_ADDR + 2, used);
+            eeprom::write_bytes(SETTINGS_ADDR, &len.to_le_bytes());
+        }
+    }
+}
@@ -1,29 +1,616 @@
+pub use crate::celsius::Celsius;
+use crate::fault::Fault;
+pub use crate::thermocouple::TemperatureError;
+use crate::thermocouple::{decode_max6675, MAX_PLAUSIBLE_TEMP};
 use avr_delay::delay_us;
+use avrd::current::{ADCH, ADCL, ADCSRA, ADMUX};
+use core::fmt::{Display, Formatter};
+use core::marker::PhantomData;
+use core::ptr::{read_volatile, write_volatile};
 use ruduino::cores::atmega328::Spi;
+use ruduino::cores::current::SPCR;
 use ruduino::modules::HardwareSpi;
-use ruduino::cores::current::port::B2;
-use ruduino::delay::delay;
-use ruduino::Pin;
+use ruduino::cores::current::port::{B1, B2};
+use ruduino::{Pin, Register};
+use serde::{Deserialize, Serialize};
 
-type CSPin = B2;
+// one chip select per probe, the rest of the bus is shared
+type CS0 = B2;
+type CS1 = B1;
 
-pub struct Temperature {}
+#[cfg(all(feature = "max31855", feature = "max31856"))]
+compile_error!("only one thermocouple amplifier can be selected");
 
-impl Temperature {
+#[cfg(not(any(feature = "max31855", feature = "max31856")))]
+pub type Thermocouple<CS> = Temperature<CS>;
+#[cfg(feature = "max31855")]
+pub type Thermocouple<CS> = Max31855<CS>;
+#[cfg(feature = "max31856")]
+pub type Thermocouple<CS> = Max31856<CS>;
+
+pub const NUM_SENSORS: usize = 2;
+pub const SENSOR_NAMES: [&str; NUM_SENSORS] = ["PROBE 1", "PROBE 2"];
+pub const SENSORS: [fn() -> Result<Reading, TemperatureError>; NUM_SENSORS] =
+    [Thermocouple::<CS0>::read, Thermocouple::<CS1>::read];
+
+pub fn setup_sensors() {
+    Thermocouple::<CS0>::setup();
+    Thermocouple::<CS1>::setup();
+}
+
+#[cfg(feature = "max31856")]
+pub fn configure_sensors(tc_type: ThermocoupleType, averaging: Averaging, mains: MainsFilter) {
+    Max31856::<CS0>::configure(tc_type, averaging, mains);
+    Max31856::<CS1>::configure(tc_type, averaging, mains);
+}
+
+// only for showing and entering temperatures, everything is stored in Celsius
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Units {
+    #[default]
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+impl Units {
+    pub fn from_index(idx: u8) -> Option<Units> {
+        match idx {
+            0 => Some(Units::Celsius),
+            1 => Some(Units::Fahrenheit),
+            2 => Some(Units::Kelvin),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Units::Celsius => "CELSIUS",
+            Units::Fahrenheit => "FAHRENHEIT",
+            Units::Kelvin => "KELVIN",
+        }
+    }
+
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Units::Celsius => "C",
+            Units::Fahrenheit => "F",
+            Units::Kelvin => "K",
+        }
+    }
+
+    pub fn rate_suffix(&self) -> &'static str {
+        match self {
+            Units::Celsius => "C/S",
+            Units::Fahrenheit => "F/S",
+            Units::Kelvin => "K/S",
+        }
+    }
+
+    // hundredths of a degree in these units
+    pub fn from_celsius(&self, temp: Celsius) -> i32 {
+        match self {
+            Units::Celsius => temp.0,
+            Units::Fahrenheit => temp.0 * 9 / 5 + 3200,
+            Units::Kelvin => temp.0 + 27315,
+        }
+    }
+
+    pub fn to_celsius(&self, value: i32) -> Celsius {
+        match self {
+            Units::Celsius => Celsius(value),
+            Units::Fahrenheit => Celsius((value - 3200) * 5 / 9),
+            Units::Kelvin => Celsius(value - 27315),
+        }
+    }
+
+    // for differences between two temperatures, no zero point to shift
+    pub fn delta_from_celsius(&self, delta: Celsius) -> i32 {
+        match self {
+            Units::Fahrenheit => delta.0 * 9 / 5,
+            Units::Celsius | Units::Kelvin => delta.0,
+        }
+    }
+
+    pub fn delta_to_celsius(&self, value: i32) -> Celsius {
+        match self {
+            Units::Fahrenheit => Celsius(value * 5 / 9),
+            Units::Celsius | Units::Kelvin => Celsius(value),
+        }
+    }
+
+    pub fn whole(&self, temp: Celsius) -> i32 {
+        self.from_celsius(temp) / 100
+    }
+
+    pub fn show(&self, temp: Celsius) -> Shown {
+        Shown(Celsius(self.from_celsius(temp)), *self)
+    }
+
+    // the knob counts whole display degrees up from the bottom of a sensible range
+    fn entry_base(&self) -> i32 {
+        match self {
+            Units::Celsius => 0,
+            Units::Fahrenheit => 32,
+            Units::Kelvin => 273,
+        }
+    }
+
+    // fahrenheit needs bigger steps to cover the same range with a u8 counter
+    pub fn entry_step(&self) -> i32 {
+        match self {
+            Units::Fahrenheit => 2,
+            Units::Celsius | Units::Kelvin => 1,
+        }
+    }
+
+    pub fn from_counter(&self, counter: u8, step: i32) -> Celsius {
+        self.to_celsius((self.entry_base() + counter as i32 * step) * 100)
+    }
+
+    pub fn to_counter(&self, temp: Celsius, step: i32) -> u8 {
+        ((self.whole(temp) - self.entry_base()) / step).clamp(0, u8::MAX as i32) as u8
+    }
+
+    // differences on the knob, a count is `hundredths` of a display degree
+    pub fn delta_from_counter(&self, counter: u8, hundredths: i32) -> Celsius {
+        self.delta_to_celsius(counter as i32 * hundredths)
+    }
+
+    // a wide band in fahrenheit is past what the counter reaches, it stops at the top
+    pub fn delta_to_counter(&self, delta: Celsius, hundredths: i32) -> u8 {
+        (self.delta_from_celsius(delta) / hundredths).clamp(0, u8::MAX as i32) as u8
+    }
+}
+
+pub struct Shown(Celsius, Units);
+
+impl Display for Shown {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}{}", self.0, self.1.suffix())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControlSource {
+    Sensor(u8),
+    Max,
+    Average,
+}
+
+impl Default for ControlSource {
+    fn default() -> Self {
+        ControlSource::Sensor(0)
+    }
+}
+
+impl ControlSource {
+    pub fn from_index(idx: u8) -> Option<ControlSource> {
+        if (idx as usize) < NUM_SENSORS {
+            return Some(ControlSource::Sensor(idx));
+        }
+        match idx as usize - NUM_SENSORS {
+            0 => Some(ControlSource::Max),
+            1 => Some(ControlSource::Average),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ControlSource::Sensor(idx) => SENSOR_NAMES.get(*idx as usize).unwrap_or(&"MISSING"),
+            ControlSource::Max => "HOTTEST",
+            ControlSource::Average => "AVERAGE",
+        }
+    }
+
+    pub fn select(
+        &self,
+        temps: &[Result<Celsius, TemperatureError>; NUM_SENSORS],
+    ) -> Result<Celsius, TemperatureError> {
+        match self {
+            ControlSource::Sensor(idx) => temps
+                .get(*idx as usize)
+                .copied()
+                .unwrap_or(Err(TemperatureError::BusFault)),
+            ControlSource::Max => {
+                let mut hottest = temps[0]?;
+                for temp in temps {
+                    hottest = hottest.max((*temp)?);
+                }
+                Ok(hottest)
+            }
+            ControlSource::Average => {
+                let mut sum = Celsius(0);
+                for temp in temps {
+                    sum = sum + (*temp)?;
+                }
+                Ok(sum / NUM_SENSORS as i32)
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Reading {
+    pub hot_junction: Celsius,
+    // only chips that report their internal sensor fill this in
+    pub cold_junction: Option<Celsius>,
+}
+
+pub struct Temperature<CS: Pin> {
+    _cs: PhantomData<CS>,
+}
+
+impl<CS: Pin> Temperature<CS> {
+    pub fn setup() {
+        CS::set_output();
+        CS::set_high();
+        Spi::setup_master(4000000);
+    }
+
+    pub fn read() -> Result<Reading, TemperatureError> {
+        CS::set_low();
+        delay_us(100);
+        let a = Spi::receive_byte();
+        let b = Spi::receive_byte();
+        // bringing CS back up starts the next conversion
+        CS::set_high();
+        let c = decode_max6675(u16::from_be_bytes([a, b]))?;
+        // the MAX6675 compensates internally but never reports it
+        Ok(Reading {
+            hot_junction: c,
+            cold_junction: None,
+        })
+    }
+
+    pub fn read_temperature() -> Result<Celsius, TemperatureError> {
+        Self::read().map(|r| r.hot_junction)
+    }
+}
+
+pub struct Max31855<CS: Pin> {
+    _cs: PhantomData<CS>,
+}
+
+impl<CS: Pin> Max31855<CS> {
     pub fn setup() {
+        CS::set_output();
+        CS::set_high();
         Spi::setup_master(4000000);
     }
 
-    pub fn read_temperature() -> u16 {
-        B2::set_high();
+    pub fn read() -> Result<Reading, TemperatureError> {
+        CS::set_low();
         delay_us(100);
-        B2::set_low();
         let a = Spi::receive_byte();
         let b = Spi::receive_byte();
-        let mut c = u16::from_le_bytes([a, b]);
-        c &= 0b01111111_11111000;
-        c >>= 3;
+        let c = Spi::receive_byte();
+        let d = Spi::receive_byte();
+        CS::set_high();
+        let frame = u32::from_be_bytes([a, b, c, d]);
+
+        // D17 and D3 are reserved and always read 0
+        if frame == 0x0000_0000 || frame == 0xFFFF_FFFF || frame & 0x0002_0008 != 0 {
+            return Err(TemperatureError::BusFault);
+        }
+        if frame & 0x0001_0000 != 0 {
+            return Err(match frame & 0b111 {
+                0b100 => TemperatureError::ShortToVcc,
+                0b010 => TemperatureError::ShortToGnd,
+                _ => TemperatureError::OpenCircuit,
+            });
+        }
+
+        // both values are left justified two's complement, shift them down keeping the sign
+        // quarter degrees and sixteenth degrees respectively
+        let hot_junction = Celsius::from_fixed((((frame >> 16) as i16) >> 2) as i32, 2);
+        let cold_junction = Celsius::from_fixed(((frame as u16 as i16) >> 4) as i32, 4);
+
+        if hot_junction > MAX_PLAUSIBLE_TEMP {
+            return Err(TemperatureError::OutOfRange);
+        }
+        Ok(Reading {
+            hot_junction,
+            cold_junction: Some(cold_junction),
+        })
+    }
+
+    pub fn read_temperature() -> Result<Celsius, TemperatureError> {
+        Self::read().map(|r| r.hot_junction)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThermocoupleType {
+    B,
+    E,
+    J,
+    #[default]
+    K,
+    N,
+    R,
+    S,
+    T,
+}
+
+impl ThermocoupleType {
+    pub fn from_index(idx: u8) -> Option<ThermocoupleType> {
+        match idx {
+            0 => Some(ThermocoupleType::B),
+            1 => Some(ThermocoupleType::E),
+            2 => Some(ThermocoupleType::J),
+            3 => Some(ThermocoupleType::K),
+            4 => Some(ThermocoupleType::N),
+            5 => Some(ThermocoupleType::R),
+            6 => Some(ThermocoupleType::S),
+            7 => Some(ThermocoupleType::T),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThermocoupleType::B => "B TYPE",
+            ThermocoupleType::E => "E TYPE",
+            ThermocoupleType::J => "J TYPE",
+            ThermocoupleType::K => "K TYPE",
+            ThermocoupleType::N => "N TYPE",
+            ThermocoupleType::R => "R TYPE",
+            ThermocoupleType::S => "S TYPE",
+            ThermocoupleType::T => "T TYPE",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Averaging {
+    #[default]
+    One,
+    Two,
+    Four,
+    Eight,
+    Sixteen,
+}
+
+impl Averaging {
+    pub fn from_index(idx: u8) -> Option<Averaging> {
+        match idx {
+            0 => Some(Averaging::One),
+            1 => Some(Averaging::Two),
+            2 => Some(Averaging::Four),
+            3 => Some(Averaging::Eight),
+            4 => Some(Averaging::Sixteen),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Averaging::One => "1 SAMPLE",
+            Averaging::Two => "2 SAMPLES",
+            Averaging::Four => "4 SAMPLES",
+            Averaging::Eight => "8 SAMPLES",
+            Averaging::Sixteen => "16 SAMPLES",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MainsFilter {
+    #[default]
+    Hz50,
+    Hz60,
+}
+
+impl MainsFilter {
+    pub fn from_index(idx: u8) -> Option<MainsFilter> {
+        match idx {
+            0 => Some(MainsFilter::Hz50),
+            1 => Some(MainsFilter::Hz60),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MainsFilter::Hz50 => "50 HZ",
+            MainsFilter::Hz60 => "60 HZ",
+        }
+    }
+}
+
+const MAX31856_CR0: u8 = 0x00;
+const MAX31856_CR1: u8 = 0x01;
+const MAX31856_MASK: u8 = 0x02;
+const MAX31856_CJTH: u8 = 0x0A;
+const MAX31856_WRITE: u8 = 0x80;
+
+const MAX31856_CR0_CMODE: u8 = 0b1000_0000;
+const MAX31856_CR0_OCFAULT: u8 = 0b0001_0000;
+const MAX31856_CR0_50HZ: u8 = 0b0000_0001;
+
+const MAX31856_SR_RANGE: u8 = 0b1100_1100;
+const MAX31856_SR_OVUV: u8 = 0b0000_0010;
+const MAX31856_SR_OPEN: u8 = 0b0000_0001;
+
+pub struct Max31856<CS: Pin> {
+    _cs: PhantomData<CS>,
+}
+
+impl<CS: Pin> Max31856<CS> {
+    pub fn setup() {
+        CS::set_output();
+        CS::set_high();
+        Spi::setup_master(4000000);
+        // the MAX31856 only talks SPI mode 1 or 3
+        unsafe { SPCR::set(SPCR::CPHA) }
+        Self::configure(
+            ThermocoupleType::default(),
+            Averaging::default(),
+            MainsFilter::default(),
+        );
+    }
+
+    fn write_register(reg: u8, data: u8) {
+        CS::set_low();
+        Spi::send_byte(reg | MAX31856_WRITE);
+        Spi::send_byte(data);
+        CS::set_high();
+    }
+
+    pub fn configure(tc_type: ThermocoupleType, averaging: Averaging, mains: MainsFilter) {
+        let mut cr0 = MAX31856_CR0_OCFAULT;
+        if mains == MainsFilter::Hz50 {
+            cr0 |= MAX31856_CR0_50HZ;
+        }
+        // the notch filter can only be changed while conversions are stopped
+        Self::write_register(MAX31856_CR0, cr0);
+        Self::write_register(MAX31856_CR1, ((averaging as u8) << 4) | tc_type as u8);
+        // we poll the status register, keep FAULT quiet
+        Self::write_register(MAX31856_MASK, 0xFF);
+        Self::write_register(MAX31856_CR0, cr0 | MAX31856_CR0_CMODE);
+    }
+
+    pub fn read() -> Result<Reading, TemperatureError> {
+        // CJTH, CJTL, LTCBH, LTCBM, LTCBL, SR in one burst
+        let mut regs = [0_u8; 6];
+        CS::set_low();
+        Spi::send_byte(MAX31856_CJTH);
+        for r in regs.iter_mut() {
+            *r = Spi::receive_byte();
+        }
+        CS::set_high();
+        let status = regs[5];
+
+        if regs == [0x00; 6] || regs == [0xFF; 6] {
+            return Err(TemperatureError::BusFault);
+        }
+        if status & MAX31856_SR_OPEN != 0 {
+            return Err(TemperatureError::OpenCircuit);
+        }
+        if status & MAX31856_SR_OVUV != 0 {
+            return Err(TemperatureError::OverUnderVoltage);
+        }
+        if status & MAX31856_SR_RANGE != 0 {
+            return Err(TemperatureError::OutOfRange);
+        }
+
+        // 19 bit and 14 bit left justified two's complement, 1/128 and 1/64 degrees
+        let hot_junction =
+            Celsius::from_fixed(i32::from_be_bytes([regs[2], regs[3], regs[4], 0]) >> 13, 7);
+        let cold_junction =
+            Celsius::from_fixed((i16::from_be_bytes([regs[0], regs[1]]) >> 2) as i32, 6);
+
+        if hot_junction > MAX_PLAUSIBLE_TEMP {
+            return Err(TemperatureError::OutOfRange);
+        }
+        // already linearised for the configured type
+        Ok(Reading {
+            hot_junction,
+            cold_junction: Some(cold_junction),
+        })
+    }
+
+    pub fn read_temperature() -> Result<Celsius, TemperatureError> {
+        Self::read().map(|r| r.hot_junction)
+    }
+}
+
+// 100k B3950 NTC to ground on ADC0 (C0) with a 4.7k pullup to AVcc
+const NTC_CHANNEL: u8 = 0;
+const NTC_SERIES_OHMS: f32 = 4700.0;
+const NTC_NOMINAL_OHMS: f32 = 100000.0;
+const NTC_NOMINAL_KELVIN: f32 = 298.15;
+const NTC_BETA: f32 = 3950.0;
+const ADC_MAX: u16 = 1023;
+
+const ADMUX_REFS0: u8 = 0b0100_0000;
+const ADCSRA_ADEN: u8 = 0b1000_0000;
+const ADCSRA_ADSC: u8 = 0b0100_0000;
+const ADCSRA_PRESCALE_128: u8 = 0b0000_0111;
+
+// natural log for the beta equation, no libm on this target
+fn ln(x: f32) -> f32 {
+    // split into mantissa in [1, 2) and exponent
+    let bits = x.to_bits();
+    let exponent = ((bits >> 23) & 0xFF) as i32 - 127;
+    let mantissa = f32::from_bits((bits & 0x007F_FFFF) | 0x3F80_0000);
+    let t = (mantissa - 1.0) / (mantissa + 1.0);
+    let t2 = t * t;
+    let ln_mantissa = 2.0 * t * (1.0 + t2 * (1.0 / 3.0 + t2 * (1.0 / 5.0 + t2 / 7.0)));
+    exponent as f32 * core::f32::consts::LN_2 + ln_mantissa
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NtcSettings {
+    // how far the NTC and the controlling thermocouple may drift apart
+    pub margin: Celsius,
+    // hard limit on the NTC alone
+    pub limit: Celsius,
+}
+
+impl Default for NtcSettings {
+    fn default() -> Self {
+        NtcSettings {
+            margin: Celsius::from_degrees(30),
+            limit: Celsius::from_degrees(270),
+        }
+    }
+}
+
+// independent of the SPI bus, so it keeps working when the thermocouples don't
+pub struct Ntc {}
+
+impl Ntc {
+    pub fn setup() {
+        unsafe {
+            write_volatile(ADMUX, ADMUX_REFS0 | NTC_CHANNEL);
+            write_volatile(ADCSRA, ADCSRA_ADEN | ADCSRA_PRESCALE_128);
+        }
+    }
+
+    fn read_adc() -> u16 {
+        unsafe {
+            write_volatile(ADCSRA, read_volatile(ADCSRA) | ADCSRA_ADSC);
+            while read_volatile(ADCSRA) & ADCSRA_ADSC != 0 {}
+            // ADCL has to be read first, it locks ADCH until then
+            let low = read_volatile(ADCL);
+            let high = read_volatile(ADCH);
+            u16::from_le_bytes([low, high])
+        }
+    }
+
+    pub fn read_temperature() -> Result<Celsius, TemperatureError> {
+        let adc = Self::read_adc();
+        if adc == 0 {
+            // shorted thermistor looks like an oven on fire, don't trust it either way
+            return Err(TemperatureError::OutOfRange);
+        }
+        if adc >= ADC_MAX {
+            return Err(TemperatureError::OpenCircuit);
+        }
+
+        let ohms = NTC_SERIES_OHMS * adc as f32 / (ADC_MAX - adc) as f32;
+        let kelvin = 1.0 / (1.0 / NTC_NOMINAL_KELVIN + ln(ohms / NTC_NOMINAL_OHMS) / NTC_BETA);
+        Ok(Celsius(((kelvin - 273.15) * 100.0) as i32))
+    }
 
-        c >> 2 // divide by 4
+    pub fn check(
+        ntc: Result<Celsius, TemperatureError>,
+        thermocouple: Result<Celsius, TemperatureError>,
+        settings: &NtcSettings,
+    ) -> Result<(), Fault> {
+        let ntc = ntc.map_err(Fault::NtcSensor)?;
+        if ntc > settings.limit {
+            return Err(Fault::NtcOverTemp);
+        }
+        // a broken thermocouple is handled on its own, only compare against good readings
+        if let Ok(thermocouple) = thermocouple {
+            if Celsius((ntc - thermocouple).0.abs()) > settings.margin {
+                return Err(Fault::NtcDisagree);
+            }
+        }
+        Ok(())
     }
-}
\ No newline at end of file
+}
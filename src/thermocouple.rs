@@ -0,0 +1,80 @@
+use crate::celsius::Celsius;
+
+// the oven can never legitimately get this hot, anything above is a bad reading
+pub const MAX_PLAUSIBLE_TEMP: Celsius = Celsius::from_degrees(400);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TemperatureError {
+    // D2 set, probe is disconnected
+    OpenCircuit,
+    // MAX31855 only
+    ShortToGnd,
+    ShortToVcc,
+    // MAX31856 only, input outside of the +-5V protection range
+    OverUnderVoltage,
+    // MISO stuck, or a bit that always reads 0 is set
+    BusFault,
+    OutOfRange,
+    // no good conversion for too long
+    Stale,
+}
+
+impl TemperatureError {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            TemperatureError::OpenCircuit => "PROBE OPEN",
+            TemperatureError::ShortToGnd => "SHORT TO GND",
+            TemperatureError::ShortToVcc => "SHORT TO VCC",
+            TemperatureError::OverUnderVoltage => "OVER/UNDER VOLT",
+            TemperatureError::BusFault => "SPI BUS FAULT",
+            TemperatureError::OutOfRange => "OUT OF RANGE",
+            TemperatureError::Stale => "STALE READING",
+        }
+    }
+}
+
+// a MAX6675 frame, MSB first as it comes off the bus
+pub fn decode_max6675(frame: u16) -> Result<Celsius, TemperatureError> {
+    // D15 is a dummy sign bit, MISO stuck high sets it too.
+    // all zeros is a real reading of 0C, so that one can't be told apart
+    if frame & 0b10000000_00000000 != 0 {
+        return Err(TemperatureError::BusFault);
+    }
+    if frame & 0b00000000_00000100 != 0 {
+        return Err(TemperatureError::OpenCircuit);
+    }
+
+    let mut c = frame & 0b01111111_11111000;
+    c >>= 3;
+    // quarter degrees
+    let c = Celsius::from_fixed(c as i32, 2);
+
+    if c > MAX_PLAUSIBLE_TEMP {
+        return Err(TemperatureError::OutOfRange);
+    }
+    Ok(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_zero() {
+        assert_eq!(decode_max6675(0x0000), Ok(Celsius::from_degrees(0)));
+    }
+
+    #[test]
+    fn decodes_quarter_degrees() {
+        // 100.25C is 401 quarter degrees, above the three status bits
+        assert_eq!(decode_max6675(401 << 3), Ok(Celsius(10025)));
+    }
+
+    #[test]
+    fn detects_faults() {
+        assert_eq!(decode_max6675(0xFFFF), Err(TemperatureError::BusFault));
+        assert_eq!(decode_max6675(0x8000), Err(TemperatureError::BusFault));
+        assert_eq!(decode_max6675(0x0004), Err(TemperatureError::OpenCircuit));
+        assert_eq!(decode_max6675(1601 << 3), Err(TemperatureError::OutOfRange));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::setpoint::{Phase, NUM_PHASES};
+use crate::temperature::Celsius;
+use crate::TICK_HZ;
+use serde::{Deserialize, Serialize};
+
+// how far off the curve the oven may be before it counts as not following
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Band {
+    pub above: Celsius,
+    pub below: Celsius,
+}
+
+impl Band {
+    const fn new(above: i32, below: i32) -> Band {
+        Band {
+            above: Celsius::from_degrees(above),
+            below: Celsius::from_degrees(below),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackingSettings {
+    // by phase
+    pub bands: [Band; NUM_PHASES],
+    // seconds outside the band before warning, then before giving up
+    pub warn_after: u8,
+    pub abort_after: u8,
+    pub buzzer: bool,
+}
+
+impl Default for TrackingSettings {
+    fn default() -> Self {
+        TrackingSettings {
+            // cooling faster than the curve is never a problem, and slower isn't checked
+            bands: [
+                Band::new(15, 20),
+                Band::new(10, 10),
+                Band::new(10, 15),
+                Band::new(30, 250),
+            ],
+            warn_after: 10,
+            abort_after: 30,
+            buzzer: false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Deviation {
+    High,
+    Low,
+}
+
+impl Deviation {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Deviation::High => "OVEN TOO HOT",
+            Deviation::Low => "OVEN TOO COLD",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Tracking {
+    Following,
+    Warn(Deviation),
+    Abort(Deviation),
+}
+
+// watches the measured temperature against the curve during a run
+#[derive(Default)]
+pub struct Monitor {
+    // tick the oven left the band, and which way
+    outside_since: Option<(u64, Deviation)>,
+}
+
+impl Monitor {
+    pub fn new() -> Monitor {
+        Monitor::default()
+    }
+
+    pub fn reset(&mut self) {
+        self.outside_since = None;
+    }
+
+    // `now` in timer ticks
+    pub fn update(
+        &mut self,
+        settings: &TrackingSettings,
+        phase: Phase,
+        setpoint: Celsius,
+        temp: Celsius,
+        now: u64,
+    ) -> Tracking {
+        let band = settings.bands[phase.index()];
+        // the heaters are already off on the way down, a slow cooling oven has nothing to abort
+        let deviation = if temp > setpoint + band.above && phase != Phase::Cooling {
+            Deviation::High
+        } else if temp < setpoint - band.below {
+            Deviation::Low
+        } else {
+            self.outside_since = None;
+            return Tracking::Following;
+        };
+
+        // crossing straight from one side to the other starts the count again
+        let since = match self.outside_since {
+            Some((since, was)) if was == deviation => since,
+            _ => {
+                self.outside_since = Some((now, deviation));
+                now
+            }
+        };
+        let seconds = (now - since) / TICK_HZ;
+        if seconds >= settings.abort_after as u64 {
+            Tracking::Abort(deviation)
+        } else if seconds >= settings.warn_after as u64 {
+            Tracking::Warn(deviation)
+        } else {
+            Tracking::Following
+        }
+    }
+}
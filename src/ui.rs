@@ -1,16 +1,33 @@
-use crate::profile::{CurvePoint, Profile, Profiles};
+use crate::autotune::{Autotune, AutotuneError};
+use crate::calibration::{Calibration, GAIN_ONE};
+use crate::cooling::CoolingStage;
+use crate::fault::Fault;
+use crate::panic::PanicRecord;
+use crate::pid::PidGains;
+use crate::profile::{Advance, CurvePoint, Profile, Profiles, TIMEOUT_STEP};
+use crate::temperature::{Celsius, TemperatureError, Units, NUM_SENSORS};
+use core::fmt::Formatter;
 use core::str::from_utf8_unchecked;
 use lcd::{Delay, Display, Hardware};
 
 pub fn main_menu<T: Hardware + Delay>(
     hw: &mut Display<T>,
-    temp: u16,
+    temp: Result<Celsius, TemperatureError>,
+    bay_temp: Option<Celsius>,
     counter: u8,
+    units: Units,
     cont: bool,
 ) -> bool {
     if !cont {
         hw.clear();
-        writeln!(hw, "TEMP: {}", temp).unwrap();
+        match temp {
+            Ok(temp) => write!(hw, "TEMP: {}", units.show(temp)).unwrap(),
+            Err(_) => write!(hw, "TEMP: FAULT").unwrap(),
+        }
+        if let Some(bay) = bay_temp {
+            write!(hw, " BAY {}", units.whole(bay)).unwrap();
+        }
+        writeln!(hw).unwrap();
     }
     hw.position(0, 1);
     match counter {
@@ -76,24 +93,103 @@ pub fn start_heat_confirm_menu<T: Hardware + Delay>(
 pub fn heating_menu<T: Hardware + Delay>(
     hw: &mut Display<T>,
     counter: u8,
-    temp: u16,
+    temp: Celsius,
+    temps: &[Result<Celsius, TemperatureError>; NUM_SENSORS],
     profile: &Profile,
     time_left: u16,
+    warning: Option<&str>,
+    units: Units,
     cont: bool,
 ) -> bool {
     if !cont {
         hw.clear();
-        writeln!(hw, "RUN: {}", unsafe { from_utf8_unchecked(&profile.name) }).unwrap();
+    }
+    hw.position(0, 0);
+    if let Some(warning) = warning {
+        write!(hw, "{:<16}", warning).unwrap();
+    } else {
+        write!(hw, "{}", unsafe { from_utf8_unchecked(&profile.name) }).unwrap();
+        // every probe, faulted ones that we don't control on only show up here
+        for probe in temps {
+            match probe {
+                Ok(probe) => write!(hw, " {}", units.whole(*probe)).unwrap(),
+                Err(_) => write!(hw, " ERR").unwrap(),
+            }
+        }
     }
     hw.position(0, 1);
     match counter {
         _ => {
-            write!("{}C, {}LEFT", temp, time_left);
+            write!(hw, "{}, {}LEFT", units.show(temp), time_left).unwrap();
             false
         }
     }
 }
 
+pub fn cooling_menu<T: Hardware + Delay>(
+    hw: &mut Display<T>,
+    temp: Celsius,
+    stage: CoolingStage,
+    units: Units,
+    cont: bool,
+) -> bool {
+    if !cont {
+        hw.clear();
+    }
+    hw.position(0, 0);
+    write!(hw, "COOLING {}  ", units.show(temp)).unwrap();
+    hw.position(0, 1);
+    // padded so a shorter prompt clears the longer one
+    write!(hw, "{:<16}", stage.prompt()).unwrap();
+    true
+}
+
+pub fn fault_menu<T: Hardware + Delay>(hw: &mut Display<T>, fault: Fault, cont: bool) -> bool {
+    if !cont {
+        hw.clear();
+        writeln!(hw, "{}", fault.title()).unwrap();
+    }
+    hw.position(0, 1);
+    write!(hw, "{}", fault.reason()).unwrap();
+    false
+}
+
+pub fn panic_menu<T: Hardware + Delay>(
+    hw: &mut Display<T>,
+    record: &PanicRecord,
+    cont: bool,
+) -> bool {
+    if !cont {
+        hw.clear();
+        writeln!(hw, "PANIC, RECOVERED").unwrap();
+    }
+    hw.position(0, 1);
+    write!(hw, "{}", record).unwrap();
+    false
+}
+
+pub fn fault_log_menu<T: Hardware + Delay>(
+    hw: &mut Display<T>,
+    back: u8,
+    code: Option<u8>,
+    cont: bool,
+) -> bool {
+    if !cont {
+        hw.clear();
+    }
+    hw.position(0, 0);
+    write!(hw, "FAULT LOG -{}   ", back).unwrap();
+    hw.position(0, 1);
+    match code {
+        Some(code) => write!(hw, "{}            ", Fault::describe(code)).unwrap(),
+        None => {
+            write!(hw, "EMPTY           ").unwrap();
+            return false;
+        }
+    }
+    true
+}
+
 pub fn cancel_heat_menu<T: Hardware + Delay>(
     hw: &mut Display<T>,
     counter: u8,
@@ -217,6 +313,7 @@ pub fn edit_profile_points_select_menu<T: Hardware + Delay>(
     hw: &mut Display<T>,
     counter: u8,
     points: &[CurvePoint; 6],
+    units: Units,
     cont: bool,
 ) -> bool {
     if !cont {
@@ -235,7 +332,10 @@ pub fn edit_profile_points_select_menu<T: Hardware + Delay>(
             write!(
                 hw,
                 "*{}: {} {} {}",
-                counter, p.temp, p.time_seconds, p.disabled
+                counter,
+                units.whole(p.temp),
+                p.time_seconds,
+                p.disabled
             )
             .unwrap();
         }
@@ -248,6 +348,7 @@ pub fn edit_profile_point_edit_select_menu<T: Hardware + Delay>(
     counter: u8,
     point: &CurvePoint,
     idx: u8,
+    units: Units,
     cont: bool,
 ) -> bool {
     if !cont {
@@ -257,7 +358,7 @@ pub fn edit_profile_point_edit_select_menu<T: Hardware + Delay>(
     hw.position(0, 1);
     match counter {
         0 => {
-            write!(hw, "*01: TEMP {}", point.temp).unwrap();
+            write!(hw, "*01: TEMP {}", units.show(point.temp)).unwrap();
         }
         1 => {
             write!(hw, "*02: TIME {}", point.temp).unwrap();
@@ -266,7 +367,13 @@ pub fn edit_profile_point_edit_select_menu<T: Hardware + Delay>(
             write!(hw, "*03: DISABLED {}", point.temp).unwrap();
         }
         3 => {
-            write!(hw, "*04: GO BACK").unwrap();
+            write!(hw, "*04: ADVANCE {}", point.advance.name()).unwrap();
+        }
+        4 => {
+            write!(hw, "*05: TIMEOUT {}", point.timeout).unwrap();
+        }
+        5 => {
+            write!(hw, "*06: GO BACK").unwrap();
         }
         _ => false,
     }
@@ -278,11 +385,13 @@ pub fn edit_profile_point_edit_temp_menu<T: Hardware + Delay>(
     counter: u8,
     point: &CurvePoint,
     idx: u8,
+    max_temp: Celsius,
+    units: Units,
     cont: bool,
 ) -> bool {
     if !cont {
         hw.clear();
-        writeln!(hw, "PT-{}, TEMP {}", idx, point.temp).unwrap();
+        writeln!(hw, "PT-{}, TEMP {}", idx, units.whole(point.temp)).unwrap();
     }
     hw.position(0, 1);
     match counter {
@@ -290,7 +399,12 @@ pub fn edit_profile_point_edit_temp_menu<T: Hardware + Delay>(
             if cnt == u8::MAX {
                 return false;
             }
-            write!(hw, "{} DEG CEL", cnt).unwrap();
+            let entered = units.from_counter(cnt, units.entry_step());
+            if entered > max_temp {
+                write!(hw, "MAX {} DEG {}  ", units.whole(max_temp), units.suffix()).unwrap();
+            } else {
+                write!(hw, "{} DEG {}    ", units.whole(entered), units.suffix()).unwrap();
+            }
         }
     }
     true
@@ -343,6 +457,44 @@ pub fn edit_profile_point_edit_disabled_menu<T: Hardware + Delay>(
     true
 }
 
+pub fn edit_profile_point_edit_advance_menu<T: Hardware + Delay>(
+    hw: &mut Display<T>,
+    counter: u8,
+    point: &CurvePoint,
+    idx: u8,
+    cont: bool,
+) -> bool {
+    if !cont {
+        hw.clear();
+        writeln!(hw, "PT-{}, ADVANCE {}", idx, point.advance.name()).unwrap();
+    }
+    hw.position(0, 1);
+    match Advance::from_index(counter) {
+        Some(advance) => write!(hw, "ON: {}    ", advance.name()).unwrap(),
+        None => return false,
+    }
+    true
+}
+
+pub fn edit_profile_point_edit_timeout_menu<T: Hardware + Delay>(
+    hw: &mut Display<T>,
+    counter: u8,
+    point: &CurvePoint,
+    idx: u8,
+    cont: bool,
+) -> bool {
+    if !cont {
+        hw.clear();
+        writeln!(hw, "PT-{}, TIMEOUT {}", idx, point.timeout).unwrap();
+    }
+    hw.position(0, 1);
+    match counter {
+        0 => write!(hw, "NEVER      ").unwrap(),
+        cnt => write!(hw, "{} SECONDS ", cnt as u16 * TIMEOUT_STEP).unwrap(),
+    }
+    true
+}
+
 pub fn edit_exit_menu<T: Hardware + Delay>(hw: &mut Display<T>, counter: u8, cont: bool) -> bool {
     if !cont {
         hw.clear();
@@ -382,3 +534,261 @@ pub fn edit_save_exit_menu<T: Hardware + Delay>(
     }
     true
 }
+
+pub const CONFIG_ITEMS: [&str; 41] = [
+    "TC TYPE",
+    "TC AVERAGE",
+    "MAINS HZ",
+    "FILTER",
+    "MEDIAN N",
+    "EMA ALPHA",
+    "SPIKE LIMIT",
+    "CONTROL ON",
+    "NTC MARGIN",
+    "NTC LIMIT",
+    "MAX TEMP",
+    "UNITS",
+    "PID KP",
+    "PID KI",
+    "PID KD",
+    "LOOKAHEAD",
+    "FEED FWD",
+    "HEAT PERIOD",
+    "HEAT MIN ON",
+    "HEAT MIN OFF",
+    "HEAT MODE",
+    "BALANCE",
+    "HEAT BIAS",
+    "SAFE TEMP",
+    "OPEN DOOR AT",
+    "MAX COOL RATE",
+    "TRACK BANDS",
+    "TRACK WARN",
+    "TRACK ABORT",
+    "BUZZER",
+    "RUNAWAY RISE",
+    "RUNAWAY TIME",
+    "RUNAWAY MARGIN",
+    "TUNE RULE",
+    "AUTOTUNE",
+    "DIAG",
+    "FAULT LOG",
+    "CALIBRATE",
+    "CAL RESET",
+    "SAVE",
+    "GO BACK",
+];
+
+pub fn config_menu<T: Hardware + Delay>(hw: &mut Display<T>, counter: u8, cont: bool) -> bool {
+    if !cont {
+        hw.clear();
+        writeln!(hw, "CONFIG:").unwrap();
+    }
+    hw.position(0, 1);
+    match CONFIG_ITEMS.get(counter as usize) {
+        Some(item) => {
+            write!(hw, "*{}: {}", counter, item).unwrap();
+        }
+        None => return false,
+    }
+    true
+}
+
+pub fn config_value_menu<T: Hardware + Delay>(
+    hw: &mut Display<T>,
+    title: &str,
+    value: &str,
+    cont: bool,
+) -> bool {
+    if !cont {
+        hw.clear();
+        writeln!(hw, "{}:", title).unwrap();
+    }
+    hw.position(0, 1);
+    write!(hw, "*{}", value).unwrap();
+    true
+}
+
+// integer with a fixed number of decimal places, for showing scaled knob values
+pub struct Fixed(pub i32, pub u32);
+
+impl core::fmt::Display for Fixed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let scale = 10_i32.pow(self.1);
+        if self.1 == 0 {
+            return write!(f, "{}", self.0);
+        }
+        write!(
+            f,
+            "{}.{:0width$}",
+            self.0 / scale,
+            (self.0 % scale).abs(),
+            width = self.1 as usize
+        )
+    }
+}
+
+pub fn config_number_menu<T: Hardware + Delay>(
+    hw: &mut Display<T>,
+    title: &str,
+    value: impl core::fmt::Display,
+    unit: &str,
+    cont: bool,
+) -> bool {
+    if !cont {
+        hw.clear();
+        writeln!(hw, "{}:", title).unwrap();
+    }
+    hw.position(0, 1);
+    write!(hw, "*{} {}    ", value, unit).unwrap();
+    true
+}
+
+pub fn diagnostics_menu<T: Hardware + Delay>(
+    hw: &mut Display<T>,
+    name: &str,
+    raw: Result<Celsius, TemperatureError>,
+    filtered: Result<Celsius, TemperatureError>,
+    age_ms: Option<u64>,
+    units: Units,
+    cont: bool,
+) -> bool {
+    if !cont {
+        hw.clear();
+    }
+    hw.position(0, 0);
+    match raw {
+        Ok(raw) => write!(hw, "RAW {} ", units.show(raw)).unwrap(),
+        Err(why) => write!(hw, "{}", why.reason()).unwrap(),
+    }
+    match age_ms {
+        Some(age) => write!(hw, "{}MS", age).unwrap(),
+        None => write!(hw, "--").unwrap(),
+    }
+    hw.position(0, 1);
+    match filtered {
+        Ok(filtered) => write!(hw, "{} {}", name, units.show(filtered)).unwrap(),
+        Err(_) => write!(hw, "{} --", name).unwrap(),
+    }
+    true
+}
+
+pub fn calibrate_menu<T: Hardware + Delay>(
+    hw: &mut Display<T>,
+    point: &str,
+    raw: Result<Celsius, TemperatureError>,
+    reference: Celsius,
+    units: Units,
+    cont: bool,
+) -> bool {
+    if !cont {
+        hw.clear();
+    }
+    hw.position(0, 0);
+    match raw {
+        Ok(raw) => write!(hw, "{} READ {}  ", point, units.show(raw)).unwrap(),
+        Err(why) => write!(hw, "{}", why.reason()).unwrap(),
+    }
+    hw.position(0, 1);
+    write!(
+        hw,
+        "*REF {} DEG {} ",
+        units.whole(reference),
+        units.suffix()
+    )
+    .unwrap();
+    true
+}
+
+pub fn calibrate_confirm_menu<T: Hardware + Delay>(
+    hw: &mut Display<T>,
+    counter: u8,
+    calibration: Option<Calibration>,
+    units: Units,
+    cont: bool,
+) -> bool {
+    if !cont {
+        hw.clear();
+        match calibration {
+            Some(cal) => writeln!(
+                hw,
+                "G{}.{:04} O{}",
+                cal.gain / GAIN_ONE,
+                cal.gain % GAIN_ONE,
+                Celsius(units.delta_from_celsius(cal.offset))
+            )
+            .unwrap(),
+            None => writeln!(hw, "BAD REFERENCES").unwrap(),
+        }
+    }
+    hw.position(0, 1);
+    match counter {
+        0 => {
+            write!(hw, "APPLY?: * NO | YES").unwrap();
+        }
+        1 => {
+            write!(hw, "APPLY?: NO | * YES").unwrap();
+        }
+        _ => return false,
+    }
+    true
+}
+
+pub fn autotune_menu<T: Hardware + Delay>(
+    hw: &mut Display<T>,
+    temp: Celsius,
+    tune: &Autotune,
+    units: Units,
+    cont: bool,
+) -> bool {
+    if !cont {
+        hw.clear();
+    }
+    hw.position(0, 0);
+    write!(
+        hw,
+        "TUNE {} {} ",
+        units.whole(tune.setpoint()),
+        if tune.heating() { "HEAT" } else { "COOL" }
+    )
+    .unwrap();
+    hw.position(0, 1);
+    write!(
+        hw,
+        "{} {}/{}  ",
+        units.show(temp),
+        tune.cycles(),
+        tune.total_cycles()
+    )
+    .unwrap();
+    true
+}
+
+pub fn autotune_done_menu<T: Hardware + Delay>(
+    hw: &mut Display<T>,
+    result: Result<(), AutotuneError>,
+    gains: &PidGains,
+    cont: bool,
+) -> bool {
+    if !cont {
+        hw.clear();
+        match result {
+            Ok(()) => {
+                writeln!(hw, "TUNED, SAVED").unwrap();
+                write!(
+                    hw,
+                    "P{} I{} D{}",
+                    Fixed((gains.kp * 10.0) as i32, 1),
+                    Fixed((gains.ki * 1000.0) as i32, 3),
+                    gains.kd as i32
+                )
+                .unwrap();
+            }
+            Err(why) => {
+                writeln!(hw, "TUNE FAILED").unwrap();
+                write!(hw, "{}", why.reason()).unwrap();
+            }
+        }
+    }
+    true
+}
@@ -0,0 +1,36 @@
+use avrd::current::{MCUSR, WDTCSR};
+use core::arch::asm;
+use core::ptr::{read_volatile, write_volatile};
+use ruduino::interrupt::without_interrupts;
+
+const MCUSR_WDRF: u8 = 0b0000_1000;
+const WDTCSR_WDCE: u8 = 0b0001_0000;
+const WDTCSR_WDE: u8 = 0b0000_1000;
+// 1s, room for a settings save in one go
+const WDTCSR_TIMEOUT: u8 = 0b0000_0110;
+
+// what reset us, and the watchdog off until the boot is done.
+// after a watchdog reset it stays on at its shortest timeout, so call this first
+pub fn take_reset() -> bool {
+    without_interrupts(|| unsafe {
+        let mcusr = read_volatile(MCUSR);
+        // WDE can't be cleared while WDRF is set
+        write_volatile(MCUSR, 0);
+        // WDE has to be cleared within 4 cycles of WDCE
+        write_volatile(WDTCSR, WDTCSR_WDCE | WDTCSR_WDE);
+        write_volatile(WDTCSR, 0);
+        mcusr & MCUSR_WDRF != 0
+    })
+}
+
+pub fn enable() {
+    without_interrupts(|| unsafe {
+        kick();
+        write_volatile(WDTCSR, WDTCSR_WDCE | WDTCSR_WDE);
+        write_volatile(WDTCSR, WDTCSR_WDE | WDTCSR_TIMEOUT);
+    });
+}
+
+pub fn kick() {
+    unsafe { asm!("wdr") }
+}
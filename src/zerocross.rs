@@ -0,0 +1,55 @@
+use avrd::current::{PCICR, PCMSK2};
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicU16, AtomicU8, Ordering};
+use ruduino::cores::current::port::D4;
+use ruduino::Pin;
+
+// optocoupler from a zero-cross detector module, pulses high around every crossing
+type ZeroCrossPin = D4;
+
+const PCICR_PCIE2: u8 = 0b0000_0100;
+const PCMSK2_PCINT20: u8 = 0b0001_0000;
+
+// crossings counted since the last second
+static CROSSINGS: AtomicU16 = AtomicU16::new(0);
+// mains frequency, 0 when the pulses have stopped
+static FREQUENCY: AtomicU8 = AtomicU8::new(0);
+
+pub fn setup() {
+    ZeroCrossPin::set_input();
+    unsafe {
+        write_volatile(PCMSK2, read_volatile(PCMSK2) | PCMSK2_PCINT20);
+        write_volatile(PCICR, read_volatile(PCICR) | PCICR_PCIE2);
+    }
+}
+
+// call from the pin change interrupt, true on the start of a pulse
+pub fn crossed() -> bool {
+    if !ZeroCrossPin::is_high() {
+        return false;
+    }
+    CROSSINGS.fetch_add(1, Ordering::SeqCst);
+    true
+}
+
+// call once a second
+pub fn second() {
+    // two crossings a cycle, with some slack for a noisy detector
+    let frequency = match CROSSINGS.swap(0, Ordering::SeqCst) {
+        90..=109 => 50,
+        110..=130 => 60,
+        _ => 0,
+    };
+    FREQUENCY.store(frequency, Ordering::SeqCst);
+}
+
+pub fn frequency() -> Option<u8> {
+    match FREQUENCY.load(Ordering::SeqCst) {
+        0 => None,
+        hz => Some(hz),
+    }
+}
+
+pub fn present() -> bool {
+    frequency().is_some()
+}